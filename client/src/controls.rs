@@ -0,0 +1,394 @@
+use crate::rebind::Rebinding;
+use bevy::ecs::system::SystemParam;
+use bevy::input::gamepad::{
+    Gamepad, GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType, Gamepads,
+};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use fight_sim::input::{Button, CombinedInput, InputState, STICK_MAX, STICK_TILT};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_DEADZONE: f32 = 0.3;
+
+fn default_deadzone() -> f32 {
+    DEFAULT_DEADZONE
+}
+
+// Every local player's bindings, read from the file given by
+// `--controls` and written back to it when they are rebound. Without
+// a file the default controls are used.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Controls {
+    pub players: Vec<PlayerControls>,
+    #[serde(default)]
+    pub training: TrainingKeys,
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+// A button is held while any key or gamepad button bound to it is, and
+// tilting the left stick further than the deadzone holds the
+// directions it points in. Keys and the d-pad tilt the stick, and
+// push it all the way over while a smash key or button is held.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PlayerControls {
+    #[serde(default)]
+    pub keys: BTreeMap<Button, Vec<KeyCode>>,
+    #[serde(default)]
+    pub pad: BTreeMap<Button, Vec<GamepadButtonType>>,
+    #[serde(default)]
+    pub smash_keys: Vec<KeyCode>,
+    #[serde(default)]
+    pub smash_pad: Vec<GamepadButtonType>,
+    // Which of the connected gamepads the player uses, counting up
+    // from 0 in the order of their ids.
+    #[serde(default)]
+    pub gamepad: Option<usize>,
+    #[serde(default = "default_deadzone")]
+    pub deadzone: f32,
+}
+
+// Training mode's own keys, which no player can have bound as well.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TrainingKeys {
+    pub pause: KeyCode,
+    pub advance: KeyCode,
+    pub save: KeyCode,
+    pub restore: KeyCode,
+    pub infinite_stocks: KeyCode,
+}
+
+impl Default for TrainingKeys {
+    fn default() -> Self {
+        TrainingKeys {
+            pause: KeyCode::P,
+            advance: KeyCode::O,
+            save: KeyCode::F5,
+            restore: KeyCode::F9,
+            infinite_stocks: KeyCode::I,
+        }
+    }
+}
+
+impl TrainingKeys {
+    pub fn contains(&self, key: KeyCode) -> bool {
+        [
+            self.pause,
+            self.advance,
+            self.save,
+            self.restore,
+            self.infinite_stocks,
+        ]
+        .contains(&key)
+    }
+}
+
+#[derive(Debug)]
+pub enum ControlsError {
+    Io(io::Error),
+    Yaml(serde_yaml::Error),
+    NoPlayers,
+    BadDeadzone { player: usize, deadzone: f32 },
+    TrainingKeyBound { player: usize, key: KeyCode },
+    TooFewPlayers { local: usize, controls: usize },
+}
+
+impl fmt::Display for ControlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ControlsError as E;
+        match self {
+            E::Io(err) => write!(f, "{err}"),
+            E::Yaml(err) => write!(f, "bad controls: {err}"),
+            E::NoPlayers => write!(f, "there are no players' controls"),
+            E::BadDeadzone { player, deadzone } => write!(
+                f,
+                "player {}'s deadzone must be between 0 and 1, not {deadzone}",
+                player + 1
+            ),
+            E::TrainingKeyBound { player, key } => write!(
+                f,
+                "player {} has {key:?} bound, which is one of training mode's keys",
+                player + 1
+            ),
+            E::TooFewPlayers { local, controls } => write!(
+                f,
+                "{local} players are playing here, but only {controls} have controls"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ControlsError {}
+
+fn bindings<T: Copy>(pairs: &[(Button, &[T])]) -> BTreeMap<Button, Vec<T>> {
+    pairs
+        .iter()
+        .map(|(button, inputs)| (*button, inputs.to_vec()))
+        .collect()
+}
+
+fn default_pad() -> BTreeMap<Button, Vec<GamepadButtonType>> {
+    use GamepadButtonType as B;
+    bindings(&[
+        (Button::Jump, &[B::South, B::North]),
+        (Button::Hit, &[B::West]),
+        (Button::Special, &[B::East]),
+        (Button::Shield, &[B::LeftTrigger2, B::RightTrigger2]),
+        (Button::Left, &[B::DPadLeft]),
+        (Button::Right, &[B::DPadRight]),
+        (Button::Down, &[B::DPadDown]),
+        (Button::Up, &[B::DPadUp]),
+    ])
+}
+
+fn default_smash_pad() -> Vec<GamepadButtonType> {
+    vec![GamepadButtonType::RightTrigger]
+}
+
+impl Default for Controls {
+    // The first player plays on WASD and the first gamepad, the second
+    // on the arrow keys, the number pad and the second gamepad, and the
+    // third and fourth on the third and fourth gamepads. Left shift,
+    // right shift and the right bumper are the smash modifiers.
+    fn default() -> Self {
+        use KeyCode as K;
+        Controls {
+            players: vec![
+                PlayerControls {
+                    keys: bindings(&[
+                        (Button::Jump, &[K::Space]),
+                        (Button::Hit, &[K::J]),
+                        (Button::Shield, &[K::K]),
+                        (Button::Special, &[K::L]),
+                        (Button::Left, &[K::A]),
+                        (Button::Right, &[K::D]),
+                        (Button::Down, &[K::S]),
+                        (Button::Up, &[K::W]),
+                    ]),
+                    pad: default_pad(),
+                    smash_keys: vec![K::ShiftLeft],
+                    smash_pad: default_smash_pad(),
+                    gamepad: Some(0),
+                    deadzone: DEFAULT_DEADZONE,
+                },
+                PlayerControls {
+                    keys: bindings(&[
+                        (Button::Jump, &[K::Numpad0]),
+                        (Button::Hit, &[K::Numpad1]),
+                        (Button::Shield, &[K::Numpad2]),
+                        (Button::Special, &[K::Numpad3]),
+                        (Button::Left, &[K::Left]),
+                        (Button::Right, &[K::Right]),
+                        (Button::Down, &[K::Down]),
+                        (Button::Up, &[K::Up]),
+                    ]),
+                    pad: default_pad(),
+                    smash_keys: vec![K::ShiftRight],
+                    smash_pad: default_smash_pad(),
+                    gamepad: Some(1),
+                    deadzone: DEFAULT_DEADZONE,
+                },
+                PlayerControls {
+                    keys: BTreeMap::new(),
+                    pad: default_pad(),
+                    smash_keys: Vec::new(),
+                    smash_pad: default_smash_pad(),
+                    gamepad: Some(2),
+                    deadzone: DEFAULT_DEADZONE,
+                },
+                PlayerControls {
+                    keys: BTreeMap::new(),
+                    pad: default_pad(),
+                    smash_keys: Vec::new(),
+                    smash_pad: default_smash_pad(),
+                    gamepad: Some(3),
+                    deadzone: DEFAULT_DEADZONE,
+                },
+            ],
+            training: TrainingKeys::default(),
+            path: PathBuf::new(),
+        }
+    }
+}
+
+impl Controls {
+    pub fn load(path: &Path) -> Result<Self, ControlsError> {
+        let mut controls = match std::fs::read(path) {
+            Ok(bytes) => Self::from_yaml(&bytes)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => return Err(ControlsError::Io(err)),
+        };
+        controls.path = path.to_owned();
+        Ok(controls)
+    }
+
+    pub fn from_yaml(bytes: &[u8]) -> Result<Self, ControlsError> {
+        let controls: Controls = serde_yaml::from_slice(bytes).map_err(ControlsError::Yaml)?;
+        if controls.players.is_empty() {
+            return Err(ControlsError::NoPlayers);
+        }
+        let training = &controls.training;
+        for (player, controls) in controls.players.iter().enumerate() {
+            if !(0. ..1.).contains(&controls.deadzone) {
+                return Err(ControlsError::BadDeadzone {
+                    player,
+                    deadzone: controls.deadzone,
+                });
+            }
+            let mut keys = controls
+                .keys
+                .values()
+                .chain([&controls.smash_keys])
+                .flatten();
+            if let Some(key) = keys.find(|key| training.contains(**key)) {
+                return Err(ControlsError::TrainingKeyBound { player, key: *key });
+            }
+        }
+        Ok(controls)
+    }
+
+    pub fn check_players(&self, local: &LocalPlayers) -> Result<(), ControlsError> {
+        if local.0.len() > self.players.len() {
+            return Err(ControlsError::TooFewPlayers {
+                local: local.0.len(),
+                controls: self.players.len(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<(), ControlsError> {
+        let yaml = serde_yaml::to_string(self).map_err(ControlsError::Yaml)?;
+        std::fs::write(&self.path, yaml).map_err(ControlsError::Io)
+    }
+}
+
+// The handles of the players on this machine. The first takes the first
+// player's controls, the second the second's, and so on.
+#[derive(Resource, Debug, Clone)]
+pub struct LocalPlayers(pub Vec<usize>);
+
+impl LocalPlayers {
+    pub fn controls<'a>(
+        &self,
+        handle: usize,
+        controls: &'a Controls,
+    ) -> Option<&'a PlayerControls> {
+        let index = self.0.iter().position(|local| *local == handle)?;
+        controls.players.get(index)
+    }
+}
+
+// A stick axis as sent with the inputs, centred within the deadzone.
+fn quantise(axis: f32, deadzone: f32) -> i8 {
+    if axis.abs() <= deadzone {
+        0
+    } else {
+        (axis.clamp(-1., 1.) * STICK_MAX as f32).round() as i8
+    }
+}
+
+// Gamepads in the order players count them in.
+pub fn connected_gamepads(gamepads: &Gamepads) -> Vec<Gamepad> {
+    let mut connected: Vec<Gamepad> = gamepads.iter().collect();
+    connected.sort_by_key(|gamepad| gamepad.id);
+    connected
+}
+
+// Everything a player's controls can be bound to.
+#[derive(SystemParam)]
+pub struct Devices<'w> {
+    keys: Res<'w, Input<KeyCode>>,
+    gamepads: Res<'w, Gamepads>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+    rebinding: Res<'w, Rebinding>,
+}
+
+impl<'w> Devices<'w> {
+    // Nothing is held while the controls are being rebound.
+    pub fn read(&self, controls: &PlayerControls) -> CombinedInput {
+        let mut input = CombinedInput::new();
+        if self.rebinding.open {
+            return input;
+        }
+        let gamepad = controls
+            .gamepad
+            .and_then(|n| connected_gamepads(&self.gamepads).get(n).copied());
+        for button in Button::iter() {
+            let key_held = controls
+                .keys
+                .get(&button)
+                .into_iter()
+                .flatten()
+                .any(|key| self.keys.pressed(*key));
+            let pad_held = gamepad.is_some_and(|gamepad| {
+                controls
+                    .pad
+                    .get(&button)
+                    .into_iter()
+                    .flatten()
+                    .any(|pad| self.buttons.pressed(GamepadButton::new(gamepad, *pad)))
+            });
+            if key_held || pad_held {
+                input.set(button, ButtonState::Pressed);
+            }
+        }
+        let smash = controls
+            .smash_keys
+            .iter()
+            .any(|key| self.keys.pressed(*key))
+            || gamepad.is_some_and(|gamepad| {
+                controls
+                    .smash_pad
+                    .iter()
+                    .any(|pad| self.buttons.pressed(GamepadButton::new(gamepad, *pad)))
+            });
+        let mut stick = (0., 0.);
+        if let Some(gamepad) = gamepad {
+            let axis = |axis_type| {
+                self.axes
+                    .get(GamepadAxis::new(gamepad, axis_type))
+                    .unwrap_or(0.)
+            };
+            stick = (
+                axis(GamepadAxisType::LeftStickX),
+                axis(GamepadAxisType::LeftStickY),
+            );
+            let deadzone = controls.deadzone;
+            for (tilted, button) in [
+                (stick.0 < -deadzone, Button::Left),
+                (stick.0 > deadzone, Button::Right),
+                (stick.1 < -deadzone, Button::Down),
+                (stick.1 > deadzone, Button::Up),
+            ] {
+                if tilted {
+                    input.set(button, ButtonState::Pressed);
+                }
+            }
+        }
+        // Keys and the d-pad push the stick as far as a tilt, or all
+        // the way over for a smash, where the stick isn't pushed already
+        let reach = if smash { STICK_MAX } else { STICK_TILT };
+        let held = |button| input.get(button) == InputState::Activated;
+        let axis = |negative, positive, analog: f32| match (held(negative), held(positive)) {
+            (true, false) if analog >= -controls.deadzone => -reach,
+            (false, true) if analog <= controls.deadzone => reach,
+            _ => quantise(analog, controls.deadzone),
+        };
+        let x = axis(Button::Left, Button::Right, stick.0);
+        let y = axis(Button::Down, Button::Up, stick.1);
+        input.set_stick(x, y);
+        input
+    }
+}
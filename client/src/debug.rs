@@ -0,0 +1,116 @@
+use bevy::log;
+use bevy::prelude::*;
+
+use fight_sim::framedata::{FrameDataAssets, FrameDataTable};
+use fight_sim::machine::types::Armour;
+use fight_sim::machine::{Character, FighterState};
+use fight_sim::projectile::Projectile;
+use fight_sim::stage::Stage;
+use fight_sim::world::{
+    Bounds, CollisionRect, Fighter, Hurtbox, Orientation, Platform, Position, Velocity,
+};
+
+// Velocities are in pixels per frame, which is too short to see.
+const VELOCITY_SCALE: f32 = 8.;
+
+const PLATFORM_COLOUR: Color = Color::BLUE;
+const HURTBOX_COLOUR: Color = Color::YELLOW;
+const HITBOX_COLOUR: Color = Color::RED;
+const BLAST_ZONE_COLOUR: Color = Color::PURPLE;
+const VELOCITY_COLOUR: Color = Color::FUCHSIA;
+
+// Whether the outlines of everything the simulation collides are drawn.
+// F3 toggles them.
+#[derive(Resource, Default, Debug)]
+pub struct DebugGizmos(pub bool);
+
+pub fn debug_gizmos_enabled(debug: Res<DebugGizmos>) -> bool {
+    debug.0
+}
+
+pub fn toggle_debug_system(keys: Res<Input<KeyCode>>, mut debug: ResMut<DebugGizmos>) {
+    if keys.just_pressed(KeyCode::F3) {
+        debug.0 = !debug.0;
+        log::info!("Debug gizmos: {}", debug.0);
+    }
+}
+
+fn armour_colour(armour: Armour) -> Color {
+    match armour {
+        Armour::None => Color::GREEN,
+        Armour::HyperArmour => Color::ORANGE,
+        Armour::Invincibility => Color::CYAN,
+        Armour::Shield => Color::WHITE,
+    }
+}
+
+fn draw_bounds(gizmos: &mut Gizmos, bounds: Bounds, colour: Color) {
+    let size = Vec2::new(bounds.width as f32, bounds.height as f32);
+    let corner = Vec2::new(bounds.x as f32, bounds.y as f32);
+    gizmos.rect_2d(corner + size / 2., 0., size, colour);
+}
+
+// Collision rects coloured by armour, hurtboxes, velocities,
+// platforms and the blast zone.
+pub fn debug_gizmos_system(
+    mut gizmos: Gizmos,
+    fighters: Query<(&Position, &Velocity, &CollisionRect, &Hurtbox, &Armour), With<Fighter>>,
+    platforms: Query<&Platform>,
+    stage: Res<Stage>,
+) {
+    for (position, velocity, rect, hurtbox, armour) in fighters.iter() {
+        // A fighter's position is the bottom left corner of its body
+        let size = Vec2::new(rect.width as f32, rect.height as f32);
+        let centre = Vec2::new(position.x as f32, position.y as f32) + size / 2.;
+        gizmos.rect_2d(centre, 0., size, armour_colour(*armour));
+        draw_bounds(&mut gizmos, hurtbox.at(position), HURTBOX_COLOUR);
+        let velocity = Vec2::new(velocity.x as f32, velocity.y as f32);
+        gizmos.line_2d(centre, centre + velocity * VELOCITY_SCALE, VELOCITY_COLOUR);
+    }
+    for platform in platforms.iter() {
+        match platform.solid_bounds() {
+            Some(bounds) => draw_bounds(&mut gizmos, bounds, PLATFORM_COLOUR),
+            None => gizmos.line_2d(
+                Vec2::new(platform.x as f32, platform.y as f32),
+                Vec2::new((platform.x + platform.width) as f32, platform.y as f32),
+                PLATFORM_COLOUR,
+            ),
+        }
+    }
+    draw_bounds(&mut gizmos, stage.blast_zone, BLAST_ZONE_COLOUR);
+}
+
+pub fn debug_projectile_system(
+    mut gizmos: Gizmos,
+    projectiles: Query<(&Projectile, &Orientation, &Position)>,
+) {
+    for (projectile, orientation, position) in projectiles.iter() {
+        draw_bounds(
+            &mut gizmos,
+            projectile.hitbox.bounds.at(position, *orientation),
+            HITBOX_COLOUR,
+        );
+    }
+}
+
+// The hitboxes active on the frame the fighter is in, which are
+// the ones that were just checked for hits.
+pub fn debug_hitbox_system<C: Character>(
+    mut gizmos: Gizmos,
+    frame_data: Res<FrameDataAssets>,
+    tables: Res<Assets<FrameDataTable>>,
+    fighters: Query<(&FighterState<C::Stance>, &Orientation, &Position)>,
+) {
+    let Some(table) = tables.get(C::frame_data(&frame_data)) else {
+        return;
+    };
+    for (state, orientation, position) in fighters.iter() {
+        for hitbox in table.get(state.stance).active_hitboxes(state.countup) {
+            draw_bounds(
+                &mut gizmos,
+                hitbox.bounds.at(position, *orientation),
+                HITBOX_COLOUR,
+            );
+        }
+    }
+}
@@ -1,33 +1,88 @@
-use crate::machine::postbox::PostboxState;
-use crate::world::{Action, FightingStance, ImageAssets, Orientation, Position};
+use crate::world::ImageAssets;
 use bevy::log;
 use bevy::prelude::*;
 
-pub fn update_graphics_system(
+use fight_sim::machine::celeste::Celeste;
+use fight_sim::machine::postbox::Postbox;
+use fight_sim::machine::{Character, FighterState};
+use fight_sim::projectile::Projectile;
+use fight_sim::world::{Orientation, Position};
+
+// How a character is drawn in each of its stances.
+pub trait Sprites: Character {
+    fn sprite(stance: Self::Stance, images: &ImageAssets) -> Handle<Image>;
+}
+
+impl Sprites for Postbox {
+    fn sprite(stance: Self::Stance, images: &ImageAssets) -> Handle<Image> {
+        use fight_sim::machine::postbox::AerialStance as A;
+        use fight_sim::machine::postbox::GroundedStance as G;
+        use fight_sim::machine::postbox::Stance as S;
+        match stance {
+            S::Grounded(G::Walking | G::Dashing | G::Running | G::Crawling) => {
+                images.postbox_walk.clone()
+            }
+            S::Grounded(
+                G::Jabbing
+                | G::FTilting
+                | G::UTilting
+                | G::DTilting
+                | G::FSmashing
+                | G::USmashing
+                | G::DSmashing,
+            ) => images.postbox_jab.clone(),
+            S::Aerial(A::NAiring | A::FAiring | A::BAiring | A::UAiring | A::DAiring) => {
+                images.postbox_nair.clone()
+            }
+            _ => images.postbox_stand.clone(),
+        }
+    }
+}
+
+impl Sprites for Celeste {
+    fn sprite(stance: Self::Stance, images: &ImageAssets) -> Handle<Image> {
+        use fight_sim::machine::celeste::AerialStance as A;
+        use fight_sim::machine::celeste::GroundedStance as G;
+        use fight_sim::machine::celeste::Stance as S;
+        match stance {
+            S::Grounded(G::Walking) => images.celeste_walk.clone(),
+            S::Grounded(G::Jabbing) => images.celeste_jab.clone(),
+            S::Aerial(A::NAiring) => images.celeste_nair.clone(),
+            _ => images.celeste_stand.clone(),
+        }
+    }
+}
+
+pub fn update_graphics_system<C: Sprites>(
     mut query: Query<(
         &mut Transform,
         &mut Handle<Image>,
-        &PostboxState,
-        &Position,
+        &FighterState<C::Stance>,
         &Orientation,
+        &Position,
     )>,
     images: Res<ImageAssets>,
 ) {
-    use crate::machine::postbox::AerialStance as A;
-    use crate::machine::postbox::GroundedStance as G;
-    use crate::machine::postbox::Stance as S;
-    log::debug!("loading sprites again");
-    log::debug!("updating sprites");
-    for (mut transform, mut sprite, state, position, orientation) in query.iter_mut() {
+    log::debug!("updating {} sprites", C::NAME);
+    for (mut transform, mut sprite, state, orientation, position) in query.iter_mut() {
         transform.translation = Vec3::new(position.x as f32, position.y as f32, 0.);
         transform.rotation = match orientation {
             Orientation::Right => Quat::default(),
             Orientation::Left => Quat::from_rotation_y(std::f32::consts::PI),
         };
-        *sprite = match state.stance {
-            S::Grounded(G::Standing) => images.postbox_stand.clone(),
-            S::Grounded(G::Jabbing) => images.postbox_jab.clone(),
-            _ => images.postbox_stand.clone(),
-        }
+        *sprite = C::sprite(state.stance, &images);
+    }
+}
+
+pub fn update_projectile_graphics_system(
+    mut query: Query<(&mut Transform, &Projectile, &Orientation, &Position)>,
+) {
+    for (mut transform, projectile, orientation, position) in query.iter_mut() {
+        let bounds = projectile.hitbox.bounds.at(position, *orientation);
+        transform.translation = Vec3::new(
+            bounds.x as f32 + bounds.width as f32 / 2.,
+            bounds.y as f32 + bounds.height as f32 / 2.,
+            0.,
+        );
     }
 }
@@ -1,17 +1,16 @@
-
-use crate::world::{Allegiance, Damage, DamageText, Stocks, StocksText};
+use crate::world::{DamageText, StocksText};
 use bevy::log;
 use bevy::prelude::*;
+use fight_sim::types::MAX_PLAYERS;
+use fight_sim::world::{Allegiance, Damage, Stocks};
 use std::vec::Vec;
 
-const PLAYER_COUNT: usize = 1;
-
 pub fn update_stocks(
     stocks_query: Query<(&Allegiance, &Stocks)>,
     mut text_query: Query<(&Allegiance, &mut Text), With<StocksText>>,
 ) {
     log::debug!("Updating stocks in UI");
-    let mut stocks_vec: Vec<Option<&Stocks>> = vec![None; PLAYER_COUNT];
+    let mut stocks_vec: Vec<Option<&Stocks>> = vec![None; MAX_PLAYERS];
     for (allegiance, stocks) in stocks_query.iter() {
         stocks_vec[allegiance.handle.0 as usize] = Some(stocks);
     }
@@ -29,7 +28,7 @@ pub fn update_dmg(
     mut text_query: Query<(&Allegiance, &mut Text), With<DamageText>>,
 ) {
     log::debug!("Updating damage in UI");
-    let mut damage_vec: Vec<Option<&Damage>> = vec![None; PLAYER_COUNT];
+    let mut damage_vec: Vec<Option<&Damage>> = vec![None; MAX_PLAYERS];
     for (allegiance, damage) in dmg_query.iter() {
         damage_vec[allegiance.handle.0 as usize] = Some(damage);
     }
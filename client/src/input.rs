@@ -1,115 +1,24 @@
-use bevy::input::keyboard::KeyCode;
-use bevy::input::ButtonState;
+use crate::controls::{Controls, Devices, LocalPlayers};
 use bevy::log;
 use bevy::prelude::*;
-use bytemuck::{Pod, Zeroable};
 use ggrs::PlayerHandle;
 
-use strum_macros::EnumIter;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, Hash)]
-#[repr(u8)]
-pub enum Button {
-    Jump,
-    Hit,
-    Left,
-    Right,
-    Down,
-}
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-#[repr(u8)]
-pub enum InputState {
-    NotActivated = 0,
-    Activated = 1,
-}
-
-fn to_input_state(n: u8) -> InputState {
-    match n {
-        0 => InputState::NotActivated,
-        1 => InputState::Activated,
-        _ => panic!("Invalid InputState representation"),
-    }
-}
-
-const BITS_PER_INPUT: u8 = 1;
-
-const fn get_shift(input: Button) -> u8 {
-    BITS_PER_INPUT * input as u8
-}
-
-const BASE_MASK: u8 = 1;
-
-const fn shift_mask(input: Button) -> u8 {
-    BASE_MASK << get_shift(input)
-}
-
-fn shift_flag(input: Button, diff: InputState) -> u8 {
-    let flag = diff as u8;
-    let shift = get_shift(input);
-    log::trace!("input::shift_flag: shifting {:?} left by {:?}", diff, shift);
-    flag << shift
-}
-
-// TODO: Make this more complicated when there are multiple local players
-#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
-#[repr(C)]
-pub struct CombinedInput(u8);
-
-impl CombinedInput {
-    pub fn new() -> Self {
-        CombinedInput(0)
-    }
-
-    pub fn get(&self, button: Button) -> InputState {
-        let shift = get_shift(button);
-        let mask = shift_mask(button);
-        let flag = (self.0 & mask) >> shift;
-        to_input_state(flag)
-    }
-
-    pub fn set(&mut self, button: Button, state: ButtonState) {
-        let next_state = match state {
-            ButtonState::Pressed => InputState::Activated,
-            ButtonState::Released => InputState::NotActivated,
-        };
-        let mask = shift_mask(button);
-        let flag = shift_flag(button, next_state);
-        self.0 = (self.0 & !mask) | (flag & mask);
-    }
-}
-
-const KEYCODES_OF_INTEREST: &[KeyCode] = &[
-    KeyCode::A,
-    KeyCode::S,
-    KeyCode::D,
-    KeyCode::W,
-    KeyCode::Space,
-];
-
-const fn keycode_mapper(keycode: &KeyCode) -> Option<Button> {
-    match keycode {
-        KeyCode::A => Some(Button::Left),
-        KeyCode::S => Some(Button::Down),
-        KeyCode::D => Some(Button::Right),
-        KeyCode::W => Some(Button::Jump),
-        KeyCode::Space => Some(Button::Hit),
-        _ => None,
-    }
-}
+use fight_sim::input::{CombinedInput, InputSource};
 
+// Called once for each local player, who plays with their own controls.
 pub fn input_system(
-    In(_handle): In<PlayerHandle>,
-    keyboard_input: Res<Input<KeyCode>>,
+    In(handle): In<PlayerHandle>,
+    devices: Devices,
+    controls: Res<Controls>,
+    local: Res<LocalPlayers>,
+    mut source: ResMut<InputSource>,
 ) -> CombinedInput {
-    log::debug!("Registering inputs");
-    let mut input = CombinedInput::new();
-    // We want older events to have precedence over newer ones, thus reverse iterate
-    for keycode in KEYCODES_OF_INTEREST {
-        if keyboard_input.pressed(*keycode) {
-            input.set(keycode_mapper(keycode).unwrap(), ButtonState::Pressed);
-        }
-    }
+    log::debug!("Registering inputs for player {handle}");
+    let input = source.next(handle).unwrap_or_else(|| {
+        local
+            .controls(handle, &controls)
+            .map_or_else(CombinedInput::new, |controls| devices.read(controls))
+    });
     log::debug!("{:#?}", input);
     input
 }
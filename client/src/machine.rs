@@ -1,2 +0,0 @@
-pub mod postbox;
-pub mod types;
@@ -3,55 +3,82 @@ use bevy::prelude::*;
 use bevy::window::WindowResolution;
 use bevy_asset_loader::prelude::*;
 use bevy_fmod::FmodPlugin;
-use bevy_ggrs::{GgrsAppExtension, GgrsPlugin, GgrsSchedule, Session};
-use ggrs::{PlayerType, SessionBuilder, UdpNonBlockingSocket};
+use bevy_ggrs::{GgrsSchedule, Session};
 
-const FPS: usize = 60;
+use fight_sim::machine::celeste::Celeste;
+use fight_sim::machine::postbox::Postbox;
+use fight_sim::{checksum, framedata, replay, stage, synctest, types};
 
-mod action;
-mod death;
+mod controls;
+mod debug;
 mod graphics;
 mod hud;
 mod input;
-mod intent;
-mod machine;
-mod physics;
-mod stance;
-mod types;
+mod net;
+mod overlay;
+mod rebind;
+mod recording;
+mod select;
+mod training;
+mod viewer;
 mod world;
 
 #[derive(States, Clone, Eq, PartialEq, Debug, Hash, Default)]
 enum GameState {
     #[default]
     AssetLoading,
+    CharacterSelect,
+    Connecting,
     InGame,
 }
 
 fn main() {
-    let mut sess_build = SessionBuilder::<types::GgrsConfig>::new()
-        .with_num_players(1)
-        .with_desync_detection_mode(ggrs::DesyncDetection::On { interval: 10 })
-        .with_max_prediction_window(12)
-        .with_input_delay(2);
-
-    sess_build = sess_build.add_player(PlayerType::Local, 0).unwrap();
-    // TODO: figure out what third argument does
-    // sess_build = sess_build
-    //     .add_player(
-    //         PlayerType::Remote(SocketAddr::from_str("127.0.0.1:3002").unwrap()),
-    //         1,
-    //     )
-    //     .unwrap();
-    let socket = UdpNonBlockingSocket::bind_to_port(5005).unwrap();
-    let sess = sess_build.start_p2p_session(socket).unwrap();
+    let config = match net::NetConfig::from_env() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("fight: {err}");
+            std::process::exit(2);
+        }
+    };
+    let controls = match controls::Controls::load(&config.controls) {
+        Ok(controls) => controls,
+        Err(err) => {
+            eprintln!("fight: {}: {err}", config.controls.display());
+            std::process::exit(2);
+        }
+    };
+    let local_players = controls::LocalPlayers(config.local_handles.clone());
+    if let Err(err) = controls.check_players(&local_players) {
+        eprintln!("fight: {}: {err}", config.controls.display());
+        std::process::exit(2);
+    }
+    let sess = match net::build_session(&config) {
+        Ok(sess) => sess,
+        Err(err) => {
+            eprintln!("fight: could not start a session: {err}");
+            std::process::exit(1);
+        }
+    };
+    let mut choices = vec![fight_sim::world::CharacterId::default(); config.players];
+    for handle in config.remote_handles() {
+        choices[handle] = config.opponent;
+    }
+    // Replays already know who is playing, so they skip character select
+    let mut after_loading = GameState::CharacterSelect;
+    if let net::Mode::Replay { replay } = &config.mode {
+        choices = replay.characters.clone();
+        after_loading = GameState::Connecting;
+    }
 
     let mut app = App::new();
     log::info!("Configuring Bevy app");
     app.add_state::<GameState>()
         .add_loading_state(
-            LoadingState::new(GameState::AssetLoading).continue_to_state(GameState::InGame),
+            LoadingState::new(GameState::AssetLoading).continue_to_state(after_loading),
         )
         .add_collection_to_loading_state::<_, world::ImageAssets>(GameState::AssetLoading)
+        .add_collection_to_loading_state::<_, framedata::FrameDataAssets>(GameState::AssetLoading)
+        .add_collection_to_loading_state::<_, stage::StageAssets>(GameState::AssetLoading)
         .add_plugins((
             DefaultPlugins.set(WindowPlugin {
                 primary_window: Some(Window {
@@ -65,45 +92,129 @@ fn main() {
                 audio_banks_directory: "./fmod/Build/Desktop",
             },
         ))
-        .add_ggrs_plugin(
-            GgrsPlugin::<types::GgrsConfig>::new()
-                .with_update_frequency(FPS)
-                .with_input_system(input::input_system)
-                .register_rollback_component::<world::InputDiff>()
-                .register_rollback_component::<world::Allegiance>()
-                .register_rollback_component::<machine::postbox::PostboxState>()
-                .register_rollback_component::<machine::types::Physics>()
-                .register_rollback_component::<machine::types::Armour>()
-                .register_rollback_component::<world::Velocity>()
-                .register_rollback_component::<world::Position>()
-                .register_rollback_component::<world::Acceleration>()
-                .register_rollback_component::<world::Stocks>(),
-        )
         .insert_resource(ClearColor(Color::rgb(0.9, 0.9, 0.9)))
-        .insert_resource(types::PlayerId(0))
-        // .insert_resource(types::PlayerId(1))
-        .insert_resource(Session::P2P(sess))
-        .add_systems(OnEnter(GameState::InGame), world::startup_system)
+        .insert_resource(local_players)
+        .insert_resource(net::PendingSession(sess))
+        .insert_resource(fight_sim::world::CharacterChoices(choices))
+        .insert_resource(stage::StageChoice(config.stage))
+        .insert_resource(config.inputs.clone())
+        .insert_resource(controls)
+        .init_resource::<rebind::Rebinding>()
         .add_systems(
-            GgrsSchedule,
+            Startup,
+            (world::camera_system, rebind::setup_rebinding_system),
+        )
+        .add_systems(
+            Update,
             (
-                intent::input_diff_system,
-                machine::postbox::input_system,
-                machine::postbox::physics_system,
-                physics::acceleration_system,
-                physics::movement_system,
-                death::death_system,
+                rebind::rebinding_system,
+                rebind::update_rebinding_screen_system,
             )
                 .chain(),
         )
+        .add_systems(
+            OnEnter(GameState::CharacterSelect),
+            select::setup_select_system,
+        )
+        .add_systems(
+            Update,
+            select::select_system.run_if(in_state(GameState::CharacterSelect)),
+        )
+        .add_systems(
+            OnExit(GameState::CharacterSelect),
+            select::cleanup_select_system,
+        )
+        .add_systems(
+            OnEnter(GameState::Connecting),
+            (
+                world::startup_system,
+                net::start_session_system,
+                net::setup_connecting_system,
+            ),
+        )
+        .add_systems(
+            OnExit(GameState::Connecting),
+            net::cleanup_connecting_system,
+        )
+        .add_systems(
+            Update,
+            net::session_events_system.run_if(resource_exists::<Session<types::GgrsConfig>>()),
+        )
         .add_systems(
             Update,
             (
-                graphics::update_graphics_system,
+                world::fighter_sprite_system.run_if(resource_exists::<world::ImageAssets>()),
+                world::projectile_sprite_system,
+            ),
+        )
+        .init_resource::<debug::DebugGizmos>()
+        .add_systems(Update, debug::toggle_debug_system)
+        .add_systems(
+            Update,
+            (
+                debug::debug_gizmos_system,
+                debug::debug_hitbox_system::<Postbox>,
+                debug::debug_hitbox_system::<Celeste>,
+                debug::debug_projectile_system,
+            )
+                .run_if(in_state(GameState::InGame))
+                .run_if(debug::debug_gizmos_enabled),
+        )
+        .add_systems(
+            Update,
+            (
+                graphics::update_graphics_system::<Postbox>,
+                graphics::update_graphics_system::<Celeste>,
+                graphics::update_projectile_graphics_system,
                 hud::update_stocks,
                 hud::update_dmg,
             )
                 .run_if(in_state(GameState::InGame)),
+        );
+    fight_sim::add_simulation(&mut app, input::input_system);
+    if let net::Mode::SyncTest { .. } = config.mode {
+        app.init_resource::<synctest::SyncTestLog>().add_systems(
+            GgrsSchedule,
+            synctest::verify_system.after(checksum::checksum_system),
+        );
+    }
+    if let net::Mode::Replay { replay } = &config.mode {
+        app.insert_resource(replay::ReplayPlayer::new(replay.clone()))
+            .init_resource::<viewer::ViewerControls>()
+            .add_systems(OnEnter(GameState::Connecting), net::start_local_system)
+            .add_systems(OnEnter(GameState::InGame), overlay::setup_overlay_system)
+            .add_systems(
+                Update,
+                (viewer::viewer_system, viewer::update_overlay_system)
+                    .chain()
+                    .before(graphics::update_graphics_system::<Postbox>)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+    if let net::Mode::Training = config.mode {
+        app.init_resource::<training::TrainingControls>()
+            .add_systems(OnEnter(GameState::Connecting), net::start_local_system)
+            .add_systems(OnEnter(GameState::InGame), overlay::setup_overlay_system)
+            .add_systems(
+                Update,
+                (training::training_system, training::update_overlay_system)
+                    .chain()
+                    .before(graphics::update_graphics_system::<Postbox>)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+    if config.record.is_some() {
+        app.add_systems(
+            OnEnter(GameState::Connecting),
+            recording::start_recording_system,
+        )
+        .add_systems(
+            GgrsSchedule,
+            replay::record_system
+                .after(checksum::checksum_system)
+                .run_if(resource_exists::<replay::ReplayRecorder>()),
         )
-        .run();
+        .add_systems(Last, recording::save_recording_system);
+    }
+    app.insert_resource(config).run();
 }
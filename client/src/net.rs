@@ -0,0 +1,529 @@
+use crate::GameState;
+use bevy::log;
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+use ggrs::{GgrsEvent, PlayerType, SessionBuilder, SessionState, UdpNonBlockingSocket};
+
+use fight_sim::checksum::ChecksumHistory;
+use fight_sim::input::InputSource;
+use fight_sim::replay::{Replay, ReplayError};
+use fight_sim::stage::StageId;
+use fight_sim::types::{GgrsConfig, DEFAULT_PLAYERS, MAX_PLAYERS};
+use fight_sim::world::CharacterId;
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+const DEFAULT_PORT: u16 = 5005;
+const DEFAULT_CHECK_DISTANCE: usize = 2;
+const DEFAULT_CONTROLS: &str = "controls.yaml";
+
+// How the match is played. Each setting can be given as a command
+// line flag, or else through the environment variable next to it:
+//
+//   --port <port>            FIGHT_PORT            UDP port to listen on for remote players (5005)
+//   --players <n>            FIGHT_PLAYERS         how many players there are, 2 to 4 (2)
+//   --handle <h,...>         FIGHT_HANDLE          which players are on this machine (0)
+//   --remote <addr,...>      FIGHT_REMOTE          the other players' address, or one for each
+//   --opponent <name>        FIGHT_OPPONENT        the other players' character (postbox)
+//   --synctest               FIGHT_SYNCTEST        play both players locally, checking rollback
+//   --check-distance <n>     FIGHT_CHECK_DISTANCE  frames rolled back by the sync test (2)
+//   --inputs <source>        FIGHT_INPUTS          keyboard, random[:seed] or a script file
+//   --record <file>          FIGHT_RECORD          save a replay of the match when the game closes
+//   --replay <file>          FIGHT_REPLAY          watch a replay instead of playing
+//   --training               FIGHT_TRAINING        practice against a dummy
+//   --stage <name>           FIGHT_STAGE           platform or battlefield
+//   --controls <file>        FIGHT_CONTROLS        key and gamepad bindings (controls.yaml)
+#[derive(Resource, Debug, Clone)]
+pub struct NetConfig {
+    pub mode: Mode,
+    pub players: usize,
+    // Each local player takes the controls in the order their
+    // handles are given.
+    pub local_handles: Vec<usize>,
+    pub opponent: CharacterId,
+    pub stage: StageId,
+    pub inputs: InputSource,
+    pub record: Option<PathBuf>,
+    pub controls: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub enum Mode {
+    // `remote_addrs` has the address of each remote player, in
+    // order of their handles.
+    Online {
+        local_port: u16,
+        remote_addrs: Vec<SocketAddr>,
+    },
+    // Every player is on this machine, so there is no one to wait for.
+    Local,
+    // Every frame is rolled back and resimulated `check_distance`
+    // frames later, and the game stops if it comes out differently.
+    SyncTest {
+        check_distance: usize,
+    },
+    // Every player's inputs come from a recording.
+    Replay {
+        replay: Replay,
+    },
+    // The local player against a dummy, with the simulation paused
+    // and advanced at will.
+    Training,
+}
+
+#[derive(Debug)]
+pub enum NetConfigError {
+    MissingValue(String),
+    UnknownFlag(String),
+    MissingRemote,
+    RemoteCount { remotes: usize, addrs: usize },
+    InvalidPlayers(String),
+    InvalidPort(String),
+    InvalidRemote(String),
+    InvalidHandle(String),
+    InvalidCheckDistance(String),
+    InvalidInputs(String),
+    InvalidReplay(String),
+    UnknownCharacter(String),
+    UnknownStage(String),
+}
+
+impl fmt::Display for NetConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use NetConfigError as E;
+        match self {
+            E::MissingValue(flag) => write!(f, "`{flag}` needs a value"),
+            E::UnknownFlag(flag) => write!(f, "unknown argument `{flag}`"),
+            E::MissingRemote => write!(
+                f,
+                "no address for the other player, pass --remote or set FIGHT_REMOTE"
+            ),
+            E::RemoteCount { remotes, addrs } => write!(
+                f,
+                "{addrs} addresses given for {remotes} remote players, \
+                 give one for all of them or one for each"
+            ),
+            E::InvalidPlayers(players) => write!(
+                f,
+                "there must be 2 to {MAX_PLAYERS} players, not `{players}`"
+            ),
+            E::InvalidPort(port) => write!(f, "`{port}` is not a valid port"),
+            E::InvalidRemote(addr) => write!(f, "`{addr}` is not a valid address"),
+            E::InvalidHandle(handle) => {
+                write!(f, "`{handle}` is not a player handle, or is given twice")
+            }
+            E::InvalidCheckDistance(distance) => {
+                write!(f, "`{distance}` is not a valid check distance")
+            }
+            E::InvalidInputs(err) => write!(f, "bad inputs: {err}"),
+            E::InvalidReplay(err) => write!(f, "bad replay: {err}"),
+            E::UnknownCharacter(name) => write!(f, "unknown character `{name}`"),
+            E::UnknownStage(name) => write!(f, "unknown stage `{name}`"),
+        }
+    }
+}
+
+impl std::error::Error for NetConfigError {}
+
+fn parse_inputs(inputs: &str) -> Result<InputSource, NetConfigError> {
+    match inputs.split_once(':') {
+        _ if inputs == "keyboard" => Ok(InputSource::Device),
+        _ if inputs == "random" => Ok(InputSource::random(0)),
+        Some(("random", seed)) => seed
+            .parse()
+            .map(InputSource::random)
+            .map_err(|_| NetConfigError::InvalidInputs(format!("`{seed}` is not a seed"))),
+        _ => {
+            let script = std::fs::read_to_string(inputs)
+                .map_err(|err| NetConfigError::InvalidInputs(format!("{inputs}: {err}")))?;
+            InputSource::parse_script(&script)
+                .map_err(|err| NetConfigError::InvalidInputs(format!("{inputs}: {err}")))
+        }
+    }
+}
+
+// A comma separated list of handles, such as `0,2`.
+fn parse_handles(handles: &str, players: usize) -> Result<Vec<usize>, NetConfigError> {
+    let mut parsed = Vec::new();
+    for handle in handles.split(',') {
+        match handle.trim().parse() {
+            Ok(n) if n < players && !parsed.contains(&n) => parsed.push(n),
+            _ => return Err(NetConfigError::InvalidHandle(handle.to_owned())),
+        }
+    }
+    Ok(parsed)
+}
+
+impl NetConfig {
+    pub fn from_env() -> Result<Self, NetConfigError> {
+        Self::parse(std::env::args().skip(1), |var| std::env::var(var).ok())
+    }
+
+    fn parse(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, NetConfigError> {
+        let mut players = env("FIGHT_PLAYERS");
+        let mut port = env("FIGHT_PORT");
+        let mut remote = env("FIGHT_REMOTE");
+        let mut handle = env("FIGHT_HANDLE");
+        let mut opponent = env("FIGHT_OPPONENT");
+        let mut stage = env("FIGHT_STAGE");
+        let mut synctest = env("FIGHT_SYNCTEST").is_some();
+        let mut training = env("FIGHT_TRAINING").is_some();
+        let mut check_distance = env("FIGHT_CHECK_DISTANCE");
+        let mut inputs = env("FIGHT_INPUTS");
+        let mut record = env("FIGHT_RECORD");
+        let mut replay = env("FIGHT_REPLAY");
+        let mut controls = env("FIGHT_CONTROLS");
+
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let slot = match flag.as_str() {
+                "--players" => &mut players,
+                "--port" => &mut port,
+                "--remote" => &mut remote,
+                "--handle" => &mut handle,
+                "--opponent" => &mut opponent,
+                "--stage" => &mut stage,
+                "--check-distance" => &mut check_distance,
+                "--inputs" => &mut inputs,
+                "--record" => &mut record,
+                "--replay" => &mut replay,
+                "--controls" => &mut controls,
+                "--synctest" => {
+                    synctest = true;
+                    continue;
+                }
+                "--training" => {
+                    training = true;
+                    continue;
+                }
+                _ => return Err(NetConfigError::UnknownFlag(flag)),
+            };
+            *slot = Some(args.next().ok_or(NetConfigError::MissingValue(flag))?);
+        }
+
+        let replay = match replay {
+            Some(path) => Some(
+                File::open(&path)
+                    .map_err(ReplayError::from)
+                    .and_then(|file| Replay::read(&mut BufReader::new(file)))
+                    .map_err(|err| NetConfigError::InvalidReplay(format!("{path}: {err}")))?,
+            ),
+            None => None,
+        };
+        // Replays already know how many players there were
+        let players = match (&replay, players) {
+            (Some(replay), _) => replay.characters.len(),
+            (None, Some(players)) => match players.parse() {
+                Ok(n) if (2..=MAX_PLAYERS).contains(&n) => n,
+                _ => return Err(NetConfigError::InvalidPlayers(players)),
+            },
+            (None, None) => DEFAULT_PLAYERS,
+        };
+        let local_handles = match handle {
+            Some(handles) => parse_handles(&handles, players)?,
+            None => vec![0],
+        };
+
+        let mode = if let Some(replay) = replay {
+            Mode::Replay { replay }
+        } else if training {
+            Mode::Training
+        } else if synctest {
+            let check_distance = match check_distance {
+                Some(distance) => distance
+                    .parse()
+                    .map_err(|_| NetConfigError::InvalidCheckDistance(distance))?,
+                None => DEFAULT_CHECK_DISTANCE,
+            };
+            Mode::SyncTest { check_distance }
+        } else {
+            let remotes = players - local_handles.len();
+            if remotes == 0 {
+                Mode::Local
+            } else {
+                let local_port = match port {
+                    Some(port) => port
+                        .parse()
+                        .map_err(|_| NetConfigError::InvalidPort(port))?,
+                    None => DEFAULT_PORT,
+                };
+                let remote = remote.ok_or(NetConfigError::MissingRemote)?;
+                let remote_addrs = remote
+                    .split(',')
+                    .map(|addr| {
+                        addr.trim()
+                            .parse()
+                            .map_err(|_| NetConfigError::InvalidRemote(addr.to_owned()))
+                    })
+                    .collect::<Result<Vec<SocketAddr>, _>>()?;
+                // Remote players on the same machine share an address
+                let remote_addrs = match remote_addrs.len() {
+                    1 => vec![remote_addrs[0]; remotes],
+                    addrs if addrs == remotes => remote_addrs,
+                    addrs => return Err(NetConfigError::RemoteCount { remotes, addrs }),
+                };
+                Mode::Online {
+                    local_port,
+                    remote_addrs,
+                }
+            }
+        };
+        let opponent = match opponent {
+            Some(name) => {
+                CharacterId::from_name(&name).ok_or(NetConfigError::UnknownCharacter(name))?
+            }
+            None => CharacterId::default(),
+        };
+        let stage = match (stage, &mode) {
+            (_, Mode::Replay { replay }) => replay.stage,
+            (Some(name), _) => {
+                StageId::from_name(&name).ok_or(NetConfigError::UnknownStage(name))?
+            }
+            (None, _) => StageId::default(),
+        };
+        let inputs = match (inputs, &mode) {
+            (Some(inputs), _) => parse_inputs(&inputs)?,
+            // Nobody is at the keyboard to play the other side of a sync test
+            (None, Mode::SyncTest { .. }) => InputSource::random(0),
+            (None, Mode::Online { .. } | Mode::Local | Mode::Replay { .. } | Mode::Training) => {
+                InputSource::Device
+            }
+        };
+        Ok(NetConfig {
+            mode,
+            players,
+            local_handles,
+            opponent,
+            stage,
+            inputs,
+            record: record.map(PathBuf::from),
+            controls: PathBuf::from(controls.as_deref().unwrap_or(DEFAULT_CONTROLS)),
+        })
+    }
+
+    pub fn remote_handles(&self) -> Vec<usize> {
+        (0..self.players)
+            .filter(|handle| !self.local_handles.contains(handle))
+            .collect()
+    }
+}
+
+// The session is built up front so that a bad port is reported
+// straight away, but only handed to GGRS once both players are
+// ready to fight.
+#[derive(Resource)]
+pub struct PendingSession(pub Option<Session<GgrsConfig>>);
+
+// Replays and training have no session, as they step through the
+// simulation themselves.
+pub fn build_session(
+    config: &NetConfig,
+) -> Result<Option<Session<GgrsConfig>>, Box<dyn std::error::Error>> {
+    let builder = SessionBuilder::<GgrsConfig>::new()
+        .with_num_players(config.players)
+        .with_max_prediction_window(12);
+    match &config.mode {
+        Mode::Online {
+            local_port,
+            remote_addrs,
+        } => p2p_session(builder, config, *local_port, remote_addrs).map(Some),
+        // Nothing is ever predicted, so nothing is rolled back, and
+        // without anyone remote there's no socket to bind
+        Mode::Local => {
+            let sess = builder.with_check_distance(0).start_synctest_session()?;
+            Ok(Some(Session::SyncTest(sess)))
+        }
+        Mode::SyncTest { check_distance } => {
+            let sess = builder
+                .with_check_distance(*check_distance)
+                .start_synctest_session()?;
+            Ok(Some(Session::SyncTest(sess)))
+        }
+        Mode::Replay { .. } | Mode::Training => Ok(None),
+    }
+}
+
+fn p2p_session(
+    builder: SessionBuilder<GgrsConfig>,
+    config: &NetConfig,
+    local_port: u16,
+    remote_addrs: &[SocketAddr],
+) -> Result<Session<GgrsConfig>, Box<dyn std::error::Error>> {
+    let socket = UdpNonBlockingSocket::bind_to_port(local_port)?;
+    let mut builder = builder
+        .with_desync_detection_mode(ggrs::DesyncDetection::On { interval: 10 })
+        .with_input_delay(2);
+    for handle in &config.local_handles {
+        builder = builder.add_player(PlayerType::Local, *handle)?;
+    }
+    for (handle, addr) in config.remote_handles().into_iter().zip(remote_addrs) {
+        builder = builder.add_player(PlayerType::Remote(*addr), handle)?;
+    }
+    Ok(Session::P2P(builder.start_p2p_session(socket)?))
+}
+
+// Without a session there is no one to wait for, so the match
+// starts straight away.
+pub fn start_local_system(mut next_state: ResMut<NextState<GameState>>) {
+    log::info!("Starting local match");
+    next_state.set(GameState::InGame);
+}
+
+pub fn start_session_system(mut commands: Commands, mut pending: ResMut<PendingSession>) {
+    let Some(sess) = pending.0.take() else {
+        return;
+    };
+    log::info!("Starting session");
+    commands.insert_resource(sess);
+}
+
+#[derive(Component)]
+pub struct ConnectingScreen {}
+
+#[derive(Component)]
+pub struct ConnectingText {}
+
+pub fn setup_connecting_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    config: Res<NetConfig>,
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            ConnectingScreen {},
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    match config.mode {
+                        Mode::Online { remote_addrs, .. } => format!(
+                            "Waiting for {}",
+                            remote_addrs
+                                .iter()
+                                .map(SocketAddr::to_string)
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                        Mode::Local => "Starting local match".to_owned(),
+                        Mode::SyncTest { .. } => "Starting sync test".to_owned(),
+                        Mode::Replay { .. } => "Starting replay".to_owned(),
+                        Mode::Training => "Starting training".to_owned(),
+                    },
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 30.0,
+                        color: Color::BLACK,
+                    },
+                ),
+                ConnectingText {},
+            ));
+        });
+}
+
+pub fn cleanup_connecting_system(
+    mut commands: Commands,
+    query: Query<Entity, With<ConnectingScreen>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Reports on the session, and starts the match once the
+// players have synchronized.
+pub fn session_events_system(
+    mut session: ResMut<Session<GgrsConfig>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut text_query: Query<&mut Text, With<ConnectingText>>,
+    history: Res<ChecksumHistory>,
+) {
+    let running = match &mut *session {
+        Session::P2P(sess) => {
+            for event in sess.events() {
+                log_event(event, &mut text_query, &history);
+            }
+            sess.current_state() == SessionState::Running
+        }
+        Session::SyncTest(_) => true,
+        Session::Spectator(sess) => sess.current_state() == SessionState::Running,
+    };
+    if *state.get() == GameState::Connecting && running {
+        log::info!("Both players are ready, starting the match");
+        next_state.set(GameState::InGame);
+    }
+}
+
+fn log_event(
+    event: GgrsEvent<GgrsConfig>,
+    text_query: &mut Query<&mut Text, With<ConnectingText>>,
+    history: &ChecksumHistory,
+) {
+    match event {
+        GgrsEvent::Synchronizing { addr, total, count } => {
+            log::debug!("Synchronizing with {addr}: {count}/{total}");
+            for mut text in text_query.iter_mut() {
+                text.sections[0].value = format!("Synchronizing with {addr}: {count}/{total}");
+            }
+        }
+        GgrsEvent::Synchronized { addr } => log::info!("Synchronized with {addr}"),
+        GgrsEvent::Disconnected { addr } => log::warn!("{addr} disconnected"),
+        GgrsEvent::NetworkInterrupted {
+            addr,
+            disconnect_timeout,
+        } => log::warn!("Lost contact with {addr}, disconnecting in {disconnect_timeout}ms"),
+        GgrsEvent::NetworkResumed { addr } => log::info!("Contact with {addr} resumed"),
+        GgrsEvent::WaitRecommendation { skip_frames } => {
+            log::debug!("Running {skip_frames} frames ahead of the other player")
+        }
+        GgrsEvent::DesyncDetected {
+            frame,
+            local_checksum,
+            remote_checksum,
+            addr,
+        } => report_desync(frame, local_checksum, remote_checksum, addr, history),
+    }
+}
+
+// Both players log what they had on the frame, so that the
+// two logs can be compared to find what diverged.
+fn report_desync(
+    frame: ggrs::Frame,
+    local_checksum: u128,
+    remote_checksum: u128,
+    addr: SocketAddr,
+    history: &ChecksumHistory,
+) {
+    log::error!(
+        "Desync with {addr} on frame {frame}: our checksum is {local_checksum:#x}, \
+         theirs is {remote_checksum:#x}"
+    );
+    match u32::try_from(frame)
+        .ok()
+        .and_then(|frame| history.get(frame))
+    {
+        Some(record) => log::error!(
+            "On frame {frame} our world hashed to {:#x}, with fighters\n{}",
+            record.checksum,
+            record.dump()
+        ),
+        None => log::error!("Frame {frame} is too old to show what our world looked like"),
+    }
+}
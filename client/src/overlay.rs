@@ -0,0 +1,110 @@
+use bevy::prelude::*;
+use std::fmt::Write;
+use strum::IntoEnumIterator;
+
+use fight_sim::input::Button;
+use fight_sim::machine::celeste::CelesteState;
+use fight_sim::machine::postbox::PostboxState;
+use fight_sim::machine::types::{Armour, Physics};
+use fight_sim::machine::{FighterState, StanceKind};
+use fight_sim::shield::{Shield, SHIELD_MAX};
+use fight_sim::world::{Allegiance, ButtonDiff, Fighter, InputDiff};
+
+// Text in the corner of the screen describing each fighter's state
+// frame by frame, for the replay viewer and training mode.
+#[derive(Component)]
+pub struct FrameDataOverlay {}
+
+pub type FighterOverlayQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Allegiance,
+        &'static InputDiff,
+        &'static Armour,
+        &'static Physics,
+        &'static Shield,
+        Option<&'static PostboxState>,
+        Option<&'static CelesteState>,
+    ),
+    With<Fighter>,
+>;
+
+pub fn setup_overlay_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 16.0,
+                color: Color::BLACK,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.),
+            top: Val::Px(10.),
+            ..default()
+        }),
+        FrameDataOverlay {},
+    ));
+}
+
+fn write_fighter<S: StanceKind>(
+    text: &mut String,
+    (allegiance, input_diff, armour, physics, shield): (
+        &Allegiance,
+        &InputDiff,
+        &Armour,
+        &Physics,
+        &Shield,
+    ),
+    state: &FighterState<S>,
+) {
+    let _ = write!(
+        text,
+        "\nP{}: {:?} countup {} countdown {} charge {} {:?} {:?} shield {}/{}\n    \
+         stick {} {}  ",
+        allegiance.handle.0 + 1,
+        state.stance,
+        state.countup,
+        state.countdown,
+        state.charge,
+        armour,
+        physics,
+        shield.health,
+        SHIELD_MAX,
+        input_diff.stick.x,
+        input_diff.stick.y
+    );
+    for button in Button::iter() {
+        let diff = input_diff.get(button);
+        if diff != ButtonDiff::NotHeld {
+            let _ = write!(text, "{button:?} {diff:?}  ");
+        }
+    }
+}
+
+// Two lines per fighter: its stance and how far into it the fighter
+// is, then which buttons it is pressing.
+pub fn fighter_lines(fighters: &FighterOverlayQuery) -> String {
+    let mut text = String::new();
+    let mut fighters: Vec<_> = fighters.iter().collect();
+    fighters.sort_by_key(|(allegiance, _, _, _, _, _, _)| allegiance.handle.0);
+    for (allegiance, input_diff, armour, physics, shield, postbox, celeste) in fighters {
+        let fighter = (allegiance, input_diff, armour, physics, shield);
+        if let Some(state) = postbox {
+            write_fighter(&mut text, fighter, state);
+        }
+        if let Some(state) = celeste {
+            write_fighter(&mut text, fighter, state);
+        }
+    }
+    text
+}
+
+pub fn set_overlay_text(overlay: &mut Query<&mut Text, With<FrameDataOverlay>>, text: String) {
+    for mut overlay in overlay.iter_mut() {
+        overlay.sections[0].value = text.clone();
+    }
+}
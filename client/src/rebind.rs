@@ -0,0 +1,223 @@
+use crate::controls::{connected_gamepads, Controls, PlayerControls};
+use bevy::input::gamepad::{GamepadButton, GamepadButtonType, Gamepads};
+use bevy::log;
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use strum::IntoEnumIterator;
+
+use fight_sim::input::Button;
+
+// The rebinding screen, opened and closed with F1. Up and Down choose a
+// button or the smash modifier, Enter binds the next key or gamepad
+// button pressed to it (or Escape gives up), Backspace clears it and Tab
+// moves on to the next player's controls. Closing the screen saves the
+// controls.
+#[derive(Resource, Default)]
+pub struct Rebinding {
+    pub open: bool,
+    player: usize,
+    selected: usize,
+    waiting: bool,
+}
+
+#[derive(Component)]
+pub struct RebindingScreen {}
+
+pub fn setup_rebinding_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::BLACK,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.),
+            bottom: Val::Px(10.),
+            ..default()
+        }),
+        RebindingScreen {},
+    ));
+}
+
+// A row of the rebinding screen: one of the buttons, or the modifier
+// which pushes digital directions all the way for smashes and dashes.
+#[derive(Copy, Clone, Debug)]
+enum Row {
+    Button(Button),
+    Smash,
+}
+
+fn rows() -> Vec<Row> {
+    Button::iter()
+        .map(Row::Button)
+        .chain([Row::Smash])
+        .collect()
+}
+
+// Binds `input` to `row` and to nothing else, so that pressing it
+// never holds two buttons at once.
+fn bind<T: PartialEq>(
+    bindings: &mut BTreeMap<Button, Vec<T>>,
+    smash: &mut Vec<T>,
+    row: Row,
+    input: T,
+) {
+    for bound in bindings.values_mut().chain([&mut *smash]) {
+        bound.retain(|bound| *bound != input);
+    }
+    match row {
+        Row::Button(button) => bindings.entry(button).or_default().push(input),
+        Row::Smash => smash.push(input),
+    }
+}
+
+fn clear(controls: &mut PlayerControls, row: Row) {
+    match row {
+        Row::Button(button) => {
+            controls.keys.remove(&button);
+            controls.pad.remove(&button);
+        }
+        Row::Smash => {
+            controls.smash_keys.clear();
+            controls.smash_pad.clear();
+        }
+    }
+}
+
+pub fn rebinding_system(
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    pad_buttons: Res<Input<GamepadButton>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut controls: ResMut<Controls>,
+) {
+    if keys.just_pressed(KeyCode::F1) {
+        rebinding.open = !rebinding.open;
+        rebinding.waiting = false;
+        if !rebinding.open {
+            match controls.save() {
+                Ok(()) => log::info!("Saved controls to {}", controls.path.display()),
+                Err(err) => log::error!(
+                    "Could not save controls to {}: {err}",
+                    controls.path.display()
+                ),
+            }
+        }
+        return;
+    }
+    if !rebinding.open {
+        return;
+    }
+
+    let rows = rows();
+    let row = rows[rebinding.selected];
+    let player = rebinding.player;
+    if rebinding.waiting {
+        let pressed_pad = pad_buttons
+            .get_just_pressed()
+            .find(|pressed| !matches!(pressed.button_type, GamepadButtonType::Other(_)));
+        if keys.just_pressed(KeyCode::Escape) {
+            rebinding.waiting = false;
+        } else if let Some(key) = keys.get_just_pressed().next() {
+            if controls.training.contains(*key) {
+                log::warn!("{key:?} is one of training mode's keys, so it can't be bound");
+            } else {
+                let controls = &mut controls.players[player];
+                bind(&mut controls.keys, &mut controls.smash_keys, row, *key);
+            }
+            rebinding.waiting = false;
+        } else if let Some(pressed) = pressed_pad {
+            let controls = &mut controls.players[player];
+            bind(
+                &mut controls.pad,
+                &mut controls.smash_pad,
+                row,
+                pressed.button_type,
+            );
+            // A player without a gamepad takes the one they pressed
+            if controls.gamepad.is_none() {
+                controls.gamepad = connected_gamepads(&gamepads)
+                    .iter()
+                    .position(|gamepad| *gamepad == pressed.gamepad);
+            }
+            rebinding.waiting = false;
+        }
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Up) {
+        rebinding.selected = (rebinding.selected + rows.len() - 1) % rows.len();
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        rebinding.selected = (rebinding.selected + 1) % rows.len();
+    }
+    if keys.just_pressed(KeyCode::Tab) {
+        rebinding.player = (player + 1) % controls.players.len();
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        clear(&mut controls.players[player], row);
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        rebinding.waiting = true;
+    }
+}
+
+pub fn update_rebinding_screen_system(
+    rebinding: Res<Rebinding>,
+    controls: Res<Controls>,
+    mut screen: Query<(&mut Text, &mut Visibility), With<RebindingScreen>>,
+) {
+    if !rebinding.is_changed() && !controls.is_changed() {
+        return;
+    }
+    let player = &controls.players[rebinding.player];
+    let mut text = format!(
+        "Controls for player {} of {}, {}\n\
+         Up/Down choose, Enter bind, Backspace clear, Tab next player, F1 save and close\n",
+        rebinding.player + 1,
+        controls.players.len(),
+        match player.gamepad {
+            Some(n) => format!("gamepad {}", n + 1),
+            None => "no gamepad".to_owned(),
+        }
+    );
+    for (index, row) in rows().into_iter().enumerate() {
+        let marker = match (index == rebinding.selected, rebinding.waiting) {
+            (true, true) => "press a key or button... ",
+            (true, false) => "> ",
+            (false, _) => "  ",
+        };
+        let (name, keys, pads) = match row {
+            Row::Button(button) => (
+                format!("{button:?}"),
+                player.keys.get(&button).map_or(&[][..], Vec::as_slice),
+                player.pad.get(&button).map_or(&[][..], Vec::as_slice),
+            ),
+            Row::Smash => (
+                "Smash".to_owned(),
+                player.smash_keys.as_slice(),
+                player.smash_pad.as_slice(),
+            ),
+        };
+        let _ = write!(text, "\n{marker}{name}:");
+        for key in keys {
+            let _ = write!(text, " {key:?}");
+        }
+        for pad in pads {
+            let _ = write!(text, " pad {pad:?}");
+        }
+    }
+    for (mut screen_text, mut visibility) in screen.iter_mut() {
+        screen_text.sections[0].value = text.clone();
+        *visibility = if rebinding.open {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
@@ -0,0 +1,44 @@
+use bevy::app::AppExit;
+use bevy::log;
+use bevy::prelude::*;
+
+use fight_sim::replay::{Replay, ReplayRecorder};
+use fight_sim::stage::StageChoice;
+use fight_sim::world::CharacterChoices;
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::net::NetConfig;
+
+// Starts recording once the characters are locked in.
+pub fn start_recording_system(
+    mut commands: Commands,
+    choices: Res<CharacterChoices>,
+    stage: Res<StageChoice>,
+) {
+    log::info!("Recording the match");
+    commands.insert_resource(ReplayRecorder(Replay::new(choices.0.clone(), stage.0)));
+}
+
+// Writes the recording out when the game closes.
+pub fn save_recording_system(
+    mut exits: EventReader<AppExit>,
+    config: Res<NetConfig>,
+    recorder: Option<Res<ReplayRecorder>>,
+) {
+    if exits.iter().next().is_none() {
+        return;
+    }
+    let (Some(path), Some(recorder)) = (&config.record, recorder) else {
+        return;
+    };
+    let result = File::create(path).and_then(|file| recorder.0.write(&mut BufWriter::new(file)));
+    match result {
+        Ok(()) => log::info!(
+            "Saved {} frames of replay to {}",
+            recorder.0.inputs.len(),
+            path.display()
+        ),
+        Err(err) => log::error!("Could not save the replay to {}: {err}", path.display()),
+    }
+}
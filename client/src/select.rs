@@ -0,0 +1,139 @@
+use crate::controls::{Controls, Devices, LocalPlayers};
+use crate::GameState;
+use bevy::log;
+use bevy::prelude::*;
+
+use fight_sim::input::{Button, CombinedInput, InputState};
+use fight_sim::world::{CharacterChoices, CharacterId};
+
+// Marks everything on the character select screen so it can be
+// cleared away once the match starts.
+#[derive(Component)]
+pub struct SelectScreen {}
+
+#[derive(Component)]
+pub struct ChoiceText {
+    player: usize,
+}
+
+fn choice_label(player: usize, character: CharacterId) -> String {
+    format!("P{}: < {} >", player + 1, character.name())
+}
+
+pub fn setup_select_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    choices: Res<CharacterChoices>,
+) {
+    log::debug!("Spawning character select screen");
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            SelectScreen {},
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Choose your fighter",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 40.0,
+                    color: Color::BLACK,
+                },
+            ));
+            for (player, character) in choices.0.iter().enumerate() {
+                parent.spawn((
+                    TextBundle::from_section(
+                        choice_label(player, *character),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 30.0,
+                            color: Color::BLACK,
+                        },
+                    ),
+                    ChoiceText { player },
+                ));
+            }
+            parent.spawn(TextBundle::from_section(
+                "Left/Right to choose, Jump to fight",
+                TextStyle {
+                    font,
+                    font_size: 20.0,
+                    color: Color::BLACK,
+                },
+            ));
+        });
+}
+
+fn cycle(character: CharacterId, step: isize) -> CharacterId {
+    let all = CharacterId::ALL;
+    let idx = all.iter().position(|c| *c == character).unwrap() as isize;
+    all[(idx + step).rem_euclid(all.len() as isize) as usize]
+}
+
+fn just_pressed(input: CombinedInput, before: CombinedInput, button: Button) -> bool {
+    input.get(button) == InputState::Activated && before.get(button) == InputState::NotActivated
+}
+
+// Each local player chooses their own fighter with their own controls,
+// and any of them can start the match. Remote players' choices can't be
+// changed from here.
+pub fn select_system(
+    devices: Devices,
+    controls: Res<Controls>,
+    local: Res<LocalPlayers>,
+    mut held: Local<Vec<CombinedInput>>,
+    mut choices: ResMut<CharacterChoices>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut text_query: Query<(&ChoiceText, &mut Text)>,
+) {
+    let inputs: Vec<CombinedInput> = local
+        .0
+        .iter()
+        .map(|handle| {
+            local
+                .controls(*handle, &controls)
+                .map_or_else(CombinedInput::new, |controls| devices.read(controls))
+        })
+        .collect();
+    held.resize(inputs.len(), CombinedInput::new());
+    let mut start = false;
+    for ((handle, input), before) in local.0.iter().zip(&inputs).zip(held.iter()) {
+        let step = if just_pressed(*input, *before, Button::Left) {
+            -1
+        } else if just_pressed(*input, *before, Button::Right) {
+            1
+        } else {
+            0
+        };
+        if step != 0 {
+            choices.0[*handle] = cycle(choices.0[*handle], step);
+            log::debug!("Player {} chose {:?}", handle + 1, choices.0[*handle]);
+        }
+        start |= just_pressed(*input, *before, Button::Jump);
+    }
+    *held = inputs;
+    for (choice, mut text) in text_query.iter_mut() {
+        text.sections[0].value = choice_label(choice.player, choices.0[choice.player]);
+    }
+    if start {
+        log::info!("Starting match with {:?}", choices.0);
+        next_state.set(GameState::Connecting);
+    }
+}
+
+pub fn cleanup_select_system(mut commands: Commands, query: Query<Entity, With<SelectScreen>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
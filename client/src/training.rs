@@ -0,0 +1,120 @@
+use crate::controls::{Controls, Devices, LocalPlayers};
+use crate::overlay::{fighter_lines, set_overlay_text, FighterOverlayQuery, FrameDataOverlay};
+use bevy::ecs::system::SystemState;
+use bevy::log;
+use bevy::prelude::*;
+
+use fight_sim::death::InfiniteStocks;
+use fight_sim::input::{CombinedInput, InputSource};
+use fight_sim::snapshot::Snapshot;
+use fight_sim::world::{CharacterChoices, FrameCount};
+use fight_sim::FPS;
+
+#[derive(Resource, Default)]
+pub struct TrainingControls {
+    pub paused: bool,
+    // Fractions of a frame left over from earlier updates.
+    accumulator: f32,
+    saved: Option<Snapshot>,
+}
+
+// Training mode runs the simulation itself rather than through GGRS, so
+// that it can be paused and advanced a frame at a time. The local players
+// play with their own controls, and the dummies from `--inputs` if it is
+// given.
+//
+// The keys to pause, advance a frame while paused, save the position,
+// restore it and toggle infinite stocks are in the controls file.
+pub fn training_system(world: &mut World) {
+    let keys = world.resource::<Input<KeyCode>>().clone();
+    let hotkeys = world.resource::<Controls>().training.clone();
+    let delta = world.resource::<Time>().delta_seconds();
+
+    if keys.just_pressed(hotkeys.infinite_stocks) {
+        let mut infinite_stocks = world.resource_mut::<InfiniteStocks>();
+        infinite_stocks.0 = !infinite_stocks.0;
+        log::info!("Infinite stocks: {}", infinite_stocks.0);
+    }
+    if keys.just_pressed(hotkeys.save) {
+        log::info!("Saving position");
+        let snapshot = Snapshot::take(world);
+        world.resource_mut::<TrainingControls>().saved = Some(snapshot);
+    }
+    if keys.just_pressed(hotkeys.restore) {
+        world.resource_scope(
+            |world, controls: Mut<TrainingControls>| match &controls.saved {
+                Some(snapshot) => snapshot.restore(world),
+                None => log::info!("No position has been saved yet"),
+            },
+        );
+    }
+
+    let mut controls = world.resource_mut::<TrainingControls>();
+    if keys.just_pressed(hotkeys.pause) {
+        controls.paused = !controls.paused;
+        controls.accumulator = 0.;
+    }
+    let frames = if controls.paused {
+        keys.just_pressed(hotkeys.advance) as u32
+    } else {
+        controls.accumulator += delta * FPS as f32;
+        let frames = controls.accumulator.floor();
+        controls.accumulator -= frames;
+        frames as u32
+    };
+
+    let players = world.resource::<CharacterChoices>().0.len();
+    let mut state = SystemState::<(Devices, Res<Controls>, Res<LocalPlayers>)>::new(world);
+    let (devices, bindings, local) = state.get(world);
+    let local_inputs: Vec<Option<CombinedInput>> = (0..players)
+        .map(|handle| {
+            local
+                .controls(handle, &bindings)
+                .map(|controls| devices.read(controls))
+        })
+        .collect();
+    for _ in 0..frames {
+        let inputs = {
+            let mut source = world.resource_mut::<InputSource>();
+            local_inputs
+                .iter()
+                .enumerate()
+                .map(|(handle, local_input)| {
+                    local_input
+                        .unwrap_or_else(|| source.next(handle).unwrap_or_else(CombinedInput::new))
+                })
+                .collect()
+        };
+        fight_sim::step_frame(world, inputs);
+    }
+}
+
+pub fn update_overlay_system(
+    controls: Res<TrainingControls>,
+    bindings: Res<Controls>,
+    infinite_stocks: Res<InfiniteStocks>,
+    frame: Res<FrameCount>,
+    fighters: FighterOverlayQuery,
+    mut overlay: Query<&mut Text, With<FrameDataOverlay>>,
+) {
+    let hotkeys = &bindings.training;
+    let header = format!(
+        "training  frame {}  {}  infinite stocks {}{}\n\
+         {:?} pause, {:?} advance, {:?} save position, {:?} restore position, \
+         {:?} infinite stocks",
+        frame.0,
+        if controls.paused { "paused" } else { "running" },
+        if infinite_stocks.0 { "on" } else { "off" },
+        if controls.saved.is_some() {
+            "  position saved"
+        } else {
+            ""
+        },
+        hotkeys.pause,
+        hotkeys.advance,
+        hotkeys.save,
+        hotkeys.restore,
+        hotkeys.infinite_stocks,
+    );
+    set_overlay_text(&mut overlay, header + &fighter_lines(&fighters));
+}
@@ -0,0 +1,103 @@
+use crate::overlay::{fighter_lines, set_overlay_text, FighterOverlayQuery, FrameDataOverlay};
+use bevy::log;
+use bevy::prelude::*;
+
+use fight_sim::replay::ReplayPlayer;
+use fight_sim::world::FrameCount;
+use fight_sim::FPS;
+
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 4.;
+
+#[derive(Resource, Debug)]
+pub struct ViewerControls {
+    pub playing: bool,
+    pub speed: f32,
+    // Fractions of a frame left over from earlier updates.
+    accumulator: f32,
+}
+
+impl Default for ViewerControls {
+    fn default() -> Self {
+        ViewerControls {
+            playing: true,
+            speed: 1.,
+            accumulator: 0.,
+        }
+    }
+}
+
+// Space plays and pauses, Up and Down change the speed, comma and
+// period step a frame at a time, Left and Right skip a second, and
+// Home goes back to the start.
+pub fn viewer_system(world: &mut World) {
+    let keys = world.resource::<Input<KeyCode>>().clone();
+    let delta = world.resource::<Time>().delta_seconds();
+    let frame = world.resource::<FrameCount>().0;
+    let second = FPS as u32;
+
+    let mut controls = world.resource_mut::<ViewerControls>();
+    if keys.just_pressed(KeyCode::Space) {
+        controls.playing = !controls.playing;
+        controls.accumulator = 0.;
+    }
+    if keys.just_pressed(KeyCode::Up) {
+        controls.speed = (controls.speed * 2.).min(MAX_SPEED);
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        controls.speed = (controls.speed / 2.).max(MIN_SPEED);
+    }
+    let target = if keys.just_pressed(KeyCode::Period) {
+        controls.playing = false;
+        frame + 1
+    } else if keys.just_pressed(KeyCode::Comma) {
+        controls.playing = false;
+        frame.saturating_sub(1)
+    } else if keys.just_pressed(KeyCode::Right) {
+        frame + second
+    } else if keys.just_pressed(KeyCode::Left) {
+        frame.saturating_sub(second)
+    } else if keys.just_pressed(KeyCode::Home) {
+        0
+    } else if controls.playing {
+        controls.accumulator += delta * controls.speed * FPS as f32;
+        let frames = controls.accumulator.floor();
+        controls.accumulator -= frames;
+        frame + frames as u32
+    } else {
+        frame
+    };
+
+    let ended = world.resource_scope(|world, mut player: Mut<ReplayPlayer>| {
+        if target != frame {
+            log::debug!("Seeking from frame {frame} to {target}");
+            player.seek(world, target);
+        }
+        world.resource::<FrameCount>().0 >= player.frames()
+    });
+    if ended {
+        world.resource_mut::<ViewerControls>().playing = false;
+    }
+}
+
+pub fn update_overlay_system(
+    controls: Res<ViewerControls>,
+    player: Res<ReplayPlayer>,
+    frame: Res<FrameCount>,
+    fighters: FighterOverlayQuery,
+    mut overlay: Query<&mut Text, With<FrameDataOverlay>>,
+) {
+    let header = format!(
+        "frame {} / {}  {}  {}x\n\
+         Space pause, Up/Down speed, comma/period step, Left/Right skip, Home restart",
+        frame.0,
+        player.frames(),
+        if controls.playing {
+            "playing"
+        } else {
+            "paused"
+        },
+        controls.speed
+    );
+    set_overlay_text(&mut overlay, header + &fighter_lines(&fighters));
+}
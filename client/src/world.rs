@@ -1,14 +1,13 @@
 use bevy::log;
 use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
 use bevy_asset_loader::prelude::*;
-use bevy_ggrs::AddRollbackCommandExtension;
-use bytemuck::{Pod, Zeroable};
 
-use std::default::Default;
-
-use crate::machine::postbox::PostboxState;
-use crate::machine::types::{Armour, Physics};
-use crate::types::*;
+use fight_sim::machine::celeste::CelesteState;
+use fight_sim::projectile::Projectile;
+use fight_sim::stage::{Stage, StageAssets, StageChoice};
+use fight_sim::types::PlayerId;
+use fight_sim::world::{spawn_fighter, spawn_stage, Allegiance, Bounds, CharacterChoices, Fighter};
 
 #[derive(AssetCollection, Resource)]
 pub struct ImageAssets {
@@ -20,178 +19,14 @@ pub struct ImageAssets {
     pub postbox_jab: Handle<Image>,
     #[asset(path = "postbox-nair.png")]
     pub postbox_nair: Handle<Image>,
-}
-
-#[derive(Component, Reflect, Default)]
-pub struct Fighter {}
-
-#[derive(Component, Reflect, Default)]
-pub struct DoesDamage {}
-
-#[derive(Component, Reflect, Default)]
-pub struct Environment {}
-
-#[derive(Component, Reflect, Default)]
-pub struct CollisionRect {
-    pub width: i32,
-    pub height: i32,
-}
-
-#[derive(Copy, Clone, Default, Reflect, Debug, PartialEq, Eq)]
-pub struct PlatformId(u8);
-
-#[derive(Debug, Component)]
-pub struct Platform {
-    pub x: i32,
-    pub y: i32,
-    pub width: i32,
-    pub id: PlatformId,
-}
-
-const FIGHTER_DIMENSIONS: i32 = 40;
-
-pub fn fighter_is_on_plat(pos: &Position, plat: &Platform) -> bool {
-    if pos.x < plat.x + plat.width
-        && pos.x + FIGHTER_DIMENSIONS > plat.x
-        && pos.y - 1 < plat.y + 1
-        && pos.y > plat.y
-    {
-        log::trace!("Character at {:?} standing on platform at {:?}", pos, plat);
-        true
-    } else {
-        log::trace!(
-            "Character at {:?} not standing on platform at {:?}",
-            pos,
-            plat
-        );
-        false
-    }
-}
-
-#[derive(Component, Reflect, Default, Debug)]
-pub struct Allegiance {
-    pub handle: PlayerId,
-}
-
-#[derive(Component, Reflect, Default)]
-pub struct Stocks {
-    pub count: u8,
-}
-
-#[derive(Component, Reflect, Default)]
-pub struct Damage {
-    pub percent: u16,
-}
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-#[repr(u16)]
-pub enum ButtonDiff {
-    NotHeld = 0,
-    Held = 1,
-    Released = 2,
-    Pressed = 3,
-}
-
-pub fn is_being_pressed(diff: ButtonDiff) -> bool {
-    diff == ButtonDiff::Held || diff == ButtonDiff::Pressed
-}
-
-#[derive(Default, Component, Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable, Reflect)]
-#[repr(C)]
-pub struct InputDiff(pub u16);
-
-// Rather than use a floating-point transform system,
-// the game logic uses integers. This is translated to
-// floats for the graphics system.
-#[derive(Debug, Component, Reflect, Default)]
-pub struct Position {
-    pub x: i32,
-    pub y: i32,
-}
-
-fn posn_to_translation(p: Position) -> Vec2 {
-    Vec2::new(p.x as f32, p.y as f32)
-}
-
-#[derive(Component, Reflect, Default)]
-pub struct Moving {}
-
-#[derive(Debug, Component, Reflect, Default)]
-pub struct Velocity {
-    pub x: i32,
-    pub y: i32,
-}
-
-#[derive(Component, Reflect, Default)]
-pub struct Accelerating {}
-
-#[derive(Debug, Component, Reflect, Default)]
-pub struct Acceleration {
-    pub x: i32,
-    pub y: i32,
-}
-
-// The Command is not the final say
-// on the behaviour of the character.
-// For example, a character who is falling
-// and actives RightTilt will do a FAir or BAir
-// depending on their orientation.
-#[derive(Debug, Reflect, Default)]
-pub enum IntentKind {
-    #[default]
-    Neutral,
-    GoRight,
-    GoLeft,
-    Jab,
-    RightTilt,
-    LeftTilt,
-    DownTilt,
-    Jump,
-    Crouch,
-    CrawlRight,
-    CrawlLeft,
-}
-
-#[derive(Component, Default, Reflect, Debug)]
-pub struct Intent(pub IntentKind);
-
-#[derive(Component, PartialEq, Eq, Copy, Clone, Debug, Reflect, Default)]
-pub enum Orientation {
-    Left,
-    #[default]
-    Right,
-}
-
-#[derive(PartialEq, Eq, Copy, Clone, Debug, Reflect, Default)]
-pub struct Jumps(pub u8);
-
-#[derive(PartialEq, Eq, Copy, Clone, Debug, Reflect, Default)]
-pub enum Action {
-    #[default]
-    Standing,
-    Jumping(Jumps),
-    Falling(Jumps),
-    Walking,
-    Jabbing,
-    NAiring(Jumps),
-    //    FAiring,
-    //    BAiring,
-    //    UpAiring,
-    //    DAiring,
-    //    FTilting,
-    //    UpTilting,
-    //    DTilting,
-    //    Jabbing,
-    //    Crouching,
-    //    Crawling,
-}
-
-#[derive(Component, Default, Reflect, Debug)]
-pub struct FightingStance {
-    pub orientation: Orientation,
-    pub action: Action,
-    pub countdown: i8,
-    pub countup: u8,
+    #[asset(path = "celeste-stand.png")]
+    pub celeste_stand: Handle<Image>,
+    #[asset(path = "celeste-walk.png")]
+    pub celeste_walk: Handle<Image>,
+    #[asset(path = "celeste-jab.png")]
+    pub celeste_jab: Handle<Image>,
+    #[asset(path = "celeste-nair.png")]
+    pub celeste_nair: Handle<Image>,
 }
 
 #[derive(Component, Default, Reflect, Debug)]
@@ -200,114 +35,182 @@ pub struct StocksText {}
 #[derive(Component, Default, Reflect, Debug)]
 pub struct DamageText {}
 
-#[derive(Component, Default, Reflect, Debug)]
-pub struct StandingOn {
-    pub platform: PlatformId,
-}
-
-pub fn startup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
-    log::debug!("Loading sprites");
-    let stand_texture = asset_server.load("postbox-stand.png");
-
+pub fn camera_system(mut commands: Commands) {
     log::debug!("Spawning camera");
     commands.spawn(Camera2dBundle::default());
-    log::debug!("Spawning fighters");
-    let _main_plat = commands.spawn((
-        Platform {
-            x: -50,
-            y: 0,
-            width: 100,
-            id: PlatformId(0),
-        },
-        SpriteBundle {
-            transform: Transform::from_translation(Vec3::new(-50., 0., 0.)),
+}
+
+pub fn startup_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    choices: Res<CharacterChoices>,
+    stage_choice: Res<StageChoice>,
+    stage_assets: Res<StageAssets>,
+    stages: Res<Assets<Stage>>,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+) {
+    let stage = stages
+        .get(stage_assets.get(stage_choice.0))
+        .expect("stages are loaded before the game starts");
+    let platforms = spawn_stage(&mut commands, stage);
+    for (platform, data) in platforms.into_iter().zip(&stage.platforms) {
+        // Soft platforms are drawn as a line along their top
+        let depth = data.depth.max(1) as f32;
+        commands.entity(platform).insert(SpriteBundle {
+            transform: Transform::from_translation(Vec3::new(
+                data.x as f32 + data.width as f32 / 2.,
+                data.y as f32 - depth / 2.,
+                -1.,
+            )),
             sprite: Sprite {
                 color: Color::rgb(0., 0., 0.),
-                custom_size: Some(Vec2::new(100., 1.)),
+                custom_size: Some(Vec2::new(data.width as f32, depth)),
                 ..default()
             },
             ..default()
+        });
+    }
+    let (camera_centre, camera_size) = bounds_rect(stage.camera);
+    commands.spawn(SpriteBundle {
+        texture: asset_server.load(&stage.background),
+        transform: Transform::from_translation(camera_centre.extend(-10.)),
+        sprite: Sprite {
+            custom_size: Some(camera_size),
+            ..default()
         },
-    ));
+        ..default()
+    });
+    for (mut transform, mut projection) in camera_query.iter_mut() {
+        transform.translation = camera_centre.extend(transform.translation.z);
+        projection.scaling_mode = ScalingMode::AutoMin {
+            min_width: camera_size.x,
+            min_height: camera_size.y,
+        };
+    }
+
+    log::debug!("Spawning fighters");
+    for (handle, character) in choices.0.iter().enumerate() {
+        spawn_fighter(&mut commands, stage, PlayerId(handle), *character);
+    }
+
+    // One panel for each player, spread evenly along the bottom
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
     commands
-        .spawn((
-            Fighter {},
-            Allegiance {
-                handle: PlayerId(0),
-            },
-            PostboxState::default(),
-            InputDiff::default(),
-            Physics::default(),
-            Armour::default(),
-            Orientation::default(),
-            Position { x: 0, y: 86 },
-            Velocity { x: 0, y: 0 },
-            Acceleration { x: 0, y: 0 },
-            StandingOn {
-                platform: PlatformId(0),
-            },
-            Stocks { count: 4 },
-            Damage { percent: 0 },
-            CollisionRect {
-                width: 80,
-                height: 80,
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::SpaceEvenly,
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(0.),
+                ..default()
             },
-            SpriteBundle {
-                texture: stand_texture,
+            ..default()
+        })
+        .with_children(|parent| {
+            for handle in 0..choices.0.len() {
+                spawn_hud_panel(parent, font.clone(), PlayerId(handle));
+            }
+        });
+}
+
+// The centre and size of a box.
+fn bounds_rect(bounds: Bounds) -> (Vec2, Vec2) {
+    let size = Vec2::new(bounds.width as f32, bounds.height as f32);
+    (
+        Vec2::new(bounds.x as f32, bounds.y as f32) + size / 2.,
+        size,
+    )
+}
+
+// Draws any fighter which isn't drawn yet, including those spawned
+// again when a replay seeks back to before they were knocked out.
+pub fn fighter_sprite_system(
+    mut commands: Commands,
+    images: Res<ImageAssets>,
+    query: Query<(Entity, Option<&CelesteState>), (With<Fighter>, Without<Handle<Image>>)>,
+) {
+    for (fighter, celeste) in query.iter() {
+        let texture = match celeste {
+            Some(_) => images.celeste_stand.clone(),
+            None => images.postbox_stand.clone(),
+        };
+        commands.entity(fighter).insert(SpriteBundle {
+            texture,
+            ..default()
+        });
+    }
+}
+
+// Projectiles are drawn as their hitbox, as there are no sprites
+// for them yet. Like fighters, they can be spawned again by a rollback
+// or a replay seeking back.
+pub fn projectile_sprite_system(
+    mut commands: Commands,
+    query: Query<(Entity, &Projectile), Without<Sprite>>,
+) {
+    for (entity, projectile) in query.iter() {
+        let bounds = projectile.hitbox.bounds;
+        commands.entity(entity).insert(SpriteBundle {
+            sprite: Sprite {
+                color: Color::ANTIQUE_WHITE,
+                custom_size: Some(Vec2::new(bounds.width as f32, bounds.height as f32)),
                 ..default()
             },
-        ))
-        .add_rollback();
+            ..default()
+        });
+    }
+}
 
-    commands
+fn spawn_hud_panel(parent: &mut ChildBuilder, font: Handle<Font>, handle: PlayerId) {
+    parent
         .spawn((
             NodeBundle {
                 style: Style {
                     width: Val::Px(160.),
-                    height: Val::Px(80.),
+                    height: Val::Px(100.),
                     border: UiRect::all(Val::Px(2.)),
+                    flex_direction: FlexDirection::Column,
                     justify_content: JustifyContent::Center,
                     align_items: AlignItems::Center,
-                    position_type: PositionType::Absolute,
-                    left: Val::Px(200.),
-                    top: Val::Px(640.),
                     ..default()
                 },
                 border_color: BorderColor(Color::BLACK),
                 background_color: BackgroundColor(Color::WHITE),
                 ..default()
             },
-            Allegiance {
-                handle: PlayerId(0),
-            },
+            Allegiance { handle },
         ))
         .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                format!("P{}", handle.0 + 1),
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 20.0,
+                    color: Color::BLACK,
+                },
+            ));
             parent.spawn((
                 TextBundle::from_section(
                     "4 stocks",
                     TextStyle {
-                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font: font.clone(),
                         font_size: 30.0,
                         color: Color::BLACK,
                     },
                 ),
-                Allegiance {
-                    handle: PlayerId(0),
-                },
+                Allegiance { handle },
                 StocksText {},
             ));
             parent.spawn((
                 TextBundle::from_section(
                     "0%",
                     TextStyle {
-                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font: font.clone(),
                         font_size: 30.0,
                         color: Color::BLACK,
                     },
                 ),
-                Allegiance {
-                    handle: PlayerId(0),
-                },
+                Allegiance { handle },
                 DamageText {},
             ));
         });
@@ -0,0 +1,268 @@
+use crate::projectile::{projectile_order, Projectile};
+use crate::rollback::{FighterQuery, FighterRecord};
+use crate::world::{Allegiance, FrameCount, Orientation, Position, Velocity};
+use bevy::log;
+use bevy::prelude::*;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+
+// Frames further back than this can no longer be rolled back to,
+// nor be reported on by GGRS.
+pub const HISTORY: u32 = 128;
+
+// A hash of everything which is rolled back, as of the end of the
+// frame. This is registered for rollback with a reflected Hash so
+// that it is what GGRS compares between the players.
+#[derive(Resource, Reflect, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[reflect(Resource, Hash)]
+pub struct Checksum(pub u64);
+
+pub type FighterChecksumQuery<'w, 's> = Query<'w, 's, FighterQuery>;
+
+pub type ProjectileChecksumQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Projectile,
+        &'static Allegiance,
+        &'static Orientation,
+        &'static Position,
+        &'static Velocity,
+    ),
+>;
+
+type ProjectileRecord = (Projectile, Allegiance, Orientation, Position, Velocity);
+
+fn fighter_records(query: &FighterChecksumQuery) -> Vec<FighterRecord> {
+    let mut fighters: Vec<FighterRecord> = query.iter().map(FighterRecord::new).collect();
+    fighters.sort_by_key(|fighter| fighter.allegiance.handle.0);
+    fighters
+}
+
+fn projectile_records(query: &ProjectileChecksumQuery) -> Vec<ProjectileRecord> {
+    let mut projectiles: Vec<ProjectileRecord> = query
+        .iter()
+        .map(
+            |(projectile, allegiance, orientation, position, velocity)| {
+                (
+                    projectile.clone(),
+                    allegiance.clone(),
+                    *orientation,
+                    position.clone(),
+                    velocity.clone(),
+                )
+            },
+        )
+        .collect();
+    projectiles
+        .sort_by_key(|(projectile, allegiance, _, _, _)| projectile_order(allegiance, projectile));
+    projectiles
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+// 64 bit FNV-1a. Unlike the standard library's hashers its output is
+// fixed, and integers are hashed as little endian bytes of a fixed
+// size, so that every build on every platform agrees on a checksum.
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(FNV_OFFSET_BASIS)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    // The signed integers are passed on to these
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    // Lengths and enum discriminants, which are hashed as 64 bits
+    // even where usize is smaller.
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+fn world_checksum(
+    frame: FrameCount,
+    fighters: &[FighterRecord],
+    projectiles: &[ProjectileRecord],
+) -> u64 {
+    let mut hasher = StableHasher::default();
+    frame.hash(&mut hasher);
+    for fighter in fighters {
+        fighter.hash(&mut hasher);
+    }
+    for projectile in projectiles {
+        projectile.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// Every rolled back component of every fighter, one fighter per line.
+fn dump_fighters(fighters: &[FighterRecord]) -> String {
+    let mut dump = String::new();
+    for fighter in fighters {
+        let FighterRecord {
+            allegiance,
+            input_diff,
+            input_buffer,
+            orientation,
+            position,
+            velocity,
+            acceleration,
+            ledge_cooldown,
+            hitstun,
+            physics,
+            armour,
+            hit_registry,
+            stocks,
+            damage,
+            shield,
+            standing_on,
+            hanging_from,
+            holding,
+            held_by,
+            postbox,
+            celeste,
+        } = fighter;
+        let state = match (postbox, celeste) {
+            (Some(state), _) => format!("{state:?}"),
+            (_, Some(state)) => format!("{state:?}"),
+            (None, None) => "no state".to_owned(),
+        };
+        writeln!(
+            dump,
+            "player {}: {state} {orientation:?} {input_diff:?} {input_buffer:?} {position:?} \
+             {velocity:?} {acceleration:?} {standing_on:?} {hanging_from:?} {ledge_cooldown:?} \
+             {holding:?} {held_by:?} {hitstun:?} {physics:?} {armour:?} {hit_registry:?} \
+             {stocks:?} {damage:?} {shield:?}",
+            allegiance.handle.0
+        )
+        .unwrap();
+    }
+    dump
+}
+
+// Every projectile, one per line.
+fn dump_projectiles(projectiles: &[ProjectileRecord]) -> String {
+    let mut dump = String::new();
+    for (projectile, allegiance, orientation, position, velocity) in projectiles {
+        writeln!(
+            dump,
+            "player {}'s projectile: {projectile:?} {orientation:?} {position:?} {velocity:?}",
+            allegiance.handle.0
+        )
+        .unwrap();
+    }
+    dump
+}
+
+// Every fighter and projectile in the world as it is now.
+pub fn dump_world(query: &FighterChecksumQuery, projectiles: &ProjectileChecksumQuery) -> String {
+    dump_fighters(&fighter_records(query)) + &dump_projectiles(&projectile_records(projectiles))
+}
+
+// A copy of the world's rolled back state, which is only
+// written out if it's asked for.
+pub struct FrameRecord {
+    pub checksum: u64,
+    fighters: Vec<FighterRecord>,
+    projectiles: Vec<ProjectileRecord>,
+}
+
+impl FrameRecord {
+    pub fn dump(&self) -> String {
+        dump_fighters(&self.fighters) + &dump_projectiles(&self.projectiles)
+    }
+}
+
+// What the world looked like at the end of each recent frame, as last
+// simulated, so that a desync GGRS reports later on can be looked into.
+#[derive(Resource, Default)]
+pub struct ChecksumHistory(BTreeMap<u32, FrameRecord>);
+
+impl ChecksumHistory {
+    pub fn get(&self, frame: u32) -> Option<&FrameRecord> {
+        self.0.get(&frame)
+    }
+}
+
+// Runs last in the GgrsSchedule.
+pub fn checksum_system(
+    frame: Res<FrameCount>,
+    mut checksum: ResMut<Checksum>,
+    mut history: ResMut<ChecksumHistory>,
+    query: FighterChecksumQuery,
+    projectiles: ProjectileChecksumQuery,
+) {
+    let fighters = fighter_records(&query);
+    let projectiles = projectile_records(&projectiles);
+    checksum.0 = world_checksum(*frame, &fighters, &projectiles);
+    log::trace!("Frame {} has checksum {:#x}", frame.0, checksum.0);
+    history.0.insert(
+        frame.0,
+        FrameRecord {
+            checksum: checksum.0,
+            fighters,
+            projectiles,
+        },
+    );
+    history.0 = history.0.split_off(&frame.0.saturating_sub(HISTORY));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(value: impl Hash) -> u64 {
+        let mut hasher = StableHasher::default();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn hashes_match_fnv_1a() {
+        let mut hasher = StableHasher::default();
+        assert_eq!(hasher.finish(), 0xcbf2_9ce4_8422_2325);
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn integers_hash_the_same_everywhere() {
+        let mut bytes = StableHasher::default();
+        bytes.write(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(hash(1usize), bytes.finish());
+        assert_eq!(hash(1u64), bytes.finish());
+        assert_eq!(hash(-1i32), hash(u32::MAX));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::framedata::{FrameDataAssets, FrameDataTable, Hitbox, HitboxKind};
+use crate::grab::{escape_frames, HeldBy, Holding};
+use crate::machine::types::Armour;
+use crate::machine::{Character, FighterState};
+use crate::physics::{cos_permille, sin_permille};
+use crate::shield::Shield;
+use crate::types::PlayerId;
+use crate::world::{
+    Allegiance, Damage, DoesDamage, HitRegistry, Hitstun, Hurtbox, Orientation, Position,
+    StandingOn, Velocity,
+};
+use bevy::log;
+use bevy::prelude::*;
+
+// Hitstun can't outlast a stance countdown.
+const MAX_HITSTUN: i32 = 120;
+
+struct Hit {
+    attacker: Entity,
+    attacker_handle: PlayerId,
+    defender: Entity,
+    defender_handle: PlayerId,
+    hitbox: Hitbox,
+    direction: Orientation,
+}
+
+// Knockback in pixels per frame, after the hit's damage has been applied.
+fn knockback(hitbox: &Hitbox, percent: u16) -> i32 {
+    hitbox.base_knockback + hitbox.knockback_growth * percent as i32 / 100
+}
+
+fn hitstun_frames(knockback: i32) -> i8 {
+    (knockback * 2).clamp(1, MAX_HITSTUN) as i8
+}
+
+fn launch_velocity(hitbox: &Hitbox, knockback: i32, direction: Orientation) -> Velocity {
+    let x = knockback * cos_permille(hitbox.angle) / 1000;
+    let y = knockback * sin_permille(hitbox.angle) / 1000;
+    Velocity {
+        x: match direction {
+            Orientation::Right => x,
+            Orientation::Left => -x,
+        },
+        y,
+    }
+}
+
+// The components of a fighter which a strike affects.
+pub type Defence<'a> = (
+    Mut<'a, Damage>,
+    Mut<'a, Velocity>,
+    Mut<'a, Hitstun>,
+    Mut<'a, Shield>,
+    &'a Armour,
+);
+
+// Lands a strike on `defender`, sent the way `direction` faces,
+// however its armour takes it.
+pub fn strike(
+    commands: &mut Commands,
+    defender: Entity,
+    hitbox: &Hitbox,
+    direction: Orientation,
+    (mut damage, mut velocity, mut hitstun, mut shield, armour): Defence,
+) {
+    match armour {
+        Armour::Invincibility => {
+            log::trace!("{:?} is invincible, ignoring the hit", defender);
+        }
+        Armour::Shield => {
+            shield.block(hitbox.damage);
+            log::trace!(
+                "{:?} blocked the hit, shield is at {}",
+                defender,
+                shield.health
+            );
+        }
+        Armour::HyperArmour => {
+            damage.percent = damage.percent.saturating_add(hitbox.damage);
+            log::trace!("{:?} armoured through the hit", defender);
+        }
+        Armour::None => {
+            damage.percent = damage.percent.saturating_add(hitbox.damage);
+            let knockback = knockback(hitbox, damage.percent);
+            *velocity = launch_velocity(hitbox, knockback, direction);
+            hitstun.frames = hitstun_frames(knockback);
+            commands.entity(defender).remove::<StandingOn>();
+            log::trace!(
+                "{:?} is now at {}%, launched at {:?}",
+                defender,
+                damage.percent,
+                *velocity
+            );
+        }
+    }
+}
+
+// Checks every active hitbox of fighters playing as `C` against
+// every other fighter's hurtbox. Fighters are visited in order of
+// player handle so that the result does not depend on the order
+// in which the ECS stores them.
+pub fn hit_system<C: Character>(
+    mut commands: Commands,
+    frame_data: Res<FrameDataAssets>,
+    tables: Res<Assets<FrameDataTable>>,
+    attacker_query: Query<
+        (
+            Entity,
+            &Allegiance,
+            &FighterState<C::Stance>,
+            &Orientation,
+            &Position,
+        ),
+        With<DoesDamage>,
+    >,
+    defender_query: Query<(Entity, &Allegiance, &Position, &Hurtbox)>,
+    mut registry_query: Query<&mut HitRegistry>,
+    mut damage_query: Query<(
+        &mut Damage,
+        &mut Velocity,
+        &mut Hitstun,
+        &mut Shield,
+        &Armour,
+    )>,
+    tether_query: Query<(Option<&Holding>, Option<&HeldBy>)>,
+) {
+    log::debug!("{} hit system beginning", C::NAME);
+    let table = tables
+        .get(C::frame_data(&frame_data))
+        .expect("frame data is loaded before the game starts");
+
+    let mut attackers: Vec<_> = attacker_query.iter().collect();
+    attackers.sort_by_key(|(_, allegiance, _, _, _)| allegiance.handle.0);
+    let mut defenders: Vec<_> = defender_query.iter().collect();
+    defenders.sort_by_key(|(_, allegiance, _, _)| allegiance.handle.0);
+
+    let mut hits = Vec::new();
+    for (attacker, attacker_allegiance, state, attacker_orientation, attacker_pos) in attackers {
+        let mut registry = registry_query.get_mut(attacker).unwrap();
+        // The first frame of a stance is the start of a new move.
+        if state.countup == 0 {
+            registry.clear();
+        }
+        let stance_data = table.get(state.stance);
+        for hitbox in stance_data.active_hitboxes(state.countup) {
+            let hitbox_bounds = hitbox.bounds.at(attacker_pos, *attacker_orientation);
+            for (defender, defender_allegiance, defender_pos, hurtbox) in defenders.iter() {
+                if *defender == attacker || registry.has_hit(&defender_allegiance.handle) {
+                    continue;
+                }
+                let hurtbox_bounds = hurtbox.at(defender_pos);
+                if hitbox_bounds.overlaps(&hurtbox_bounds) {
+                    log::debug!(
+                        "Player {:?} hit player {:?} with {:?}",
+                        attacker_allegiance.handle,
+                        defender_allegiance.handle,
+                        state.stance
+                    );
+                    registry.register(&defender_allegiance.handle);
+                    hits.push(Hit {
+                        attacker,
+                        attacker_handle: attacker_allegiance.handle,
+                        defender: *defender,
+                        defender_handle: defender_allegiance.handle,
+                        hitbox: hitbox.charged(state.charge),
+                        direction: *attacker_orientation,
+                    });
+                }
+            }
+        }
+    }
+
+    // Fighters grabbed or grabbing on this frame, as the components
+    // tethering them aren't inserted until the end of it.
+    let mut tethered: Vec<Entity> = Vec::new();
+    let is_free = |entity: Entity, tethered: &Vec<Entity>| {
+        !tethered.contains(&entity) && matches!(tether_query.get(entity), Ok((None, None)))
+    };
+    for hit in hits {
+        let (mut damage, mut velocity, hitstun, shield, armour) =
+            damage_query.get_mut(hit.defender).unwrap();
+        match hit.hitbox.kind {
+            HitboxKind::Strike => (),
+            HitboxKind::Grab => {
+                if *armour == Armour::Invincibility
+                    || !is_free(hit.attacker, &tethered)
+                    || !is_free(hit.defender, &tethered)
+                {
+                    log::trace!("{:?} could not grab {:?}", hit.attacker, hit.defender);
+                    continue;
+                }
+                log::trace!("{:?} grabbed {:?}", hit.attacker, hit.defender);
+                tethered.extend([hit.attacker, hit.defender]);
+                *velocity = Velocity { x: 0, y: 0 };
+                commands.entity(hit.attacker).insert(Holding {
+                    victim: hit.defender_handle,
+                });
+                commands.entity(hit.defender).insert(HeldBy {
+                    holder: hit.attacker_handle,
+                    escape: escape_frames(damage.percent),
+                });
+                continue;
+            }
+            HitboxKind::Pummel => {
+                let held_by_attacker = matches!(
+                    tether_query.get(hit.defender),
+                    Ok((_, Some(held_by))) if held_by.holder == hit.attacker_handle
+                );
+                if held_by_attacker {
+                    damage.percent = damage.percent.saturating_add(hit.hitbox.damage);
+                    log::trace!("{:?} pummeled to {}%", hit.defender, damage.percent);
+                }
+                continue;
+            }
+        }
+        strike(
+            &mut commands,
+            hit.defender,
+            &hit.hitbox,
+            hit.direction,
+            (damage, velocity, hitstun, shield, armour),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless;
+    use crate::machine::postbox::{AerialStance, Stance};
+
+    fn is_tumbling(app: &mut App) -> bool {
+        headless::postbox_state(app, 1).stance == Stance::Aerial(AerialStance::Tumbling)
+    }
+
+    #[test]
+    fn hitstun_lasts_after_landing() {
+        let mut app = headless::landed_app();
+        headless::stand(&mut app, 0, 0, Orientation::Right);
+        headless::stand(&mut app, 1, 40, Orientation::Left);
+        // A jab barely lifts its target, who is back down well within hitstun
+        headless::step(&mut app, 1, &["H", "-"]);
+        for _ in 0..5 {
+            if is_tumbling(&mut app) {
+                break;
+            }
+            headless::step(&mut app, 1, &[]);
+        }
+        assert!(is_tumbling(&mut app));
+
+        let frames_left = headless::postbox_state(&mut app, 1).countdown;
+        let victim = headless::fighter(&mut app, 1);
+        let mut landed = false;
+        for _ in 1..frames_left {
+            headless::step(&mut app, 1, &[]);
+            landed |= app.world.get::<StandingOn>(victim).is_some();
+            assert!(is_tumbling(&mut app));
+        }
+        assert!(landed);
+        headless::step(&mut app, 1, &[]);
+        assert!(!is_tumbling(&mut app));
+    }
+}
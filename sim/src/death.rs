@@ -0,0 +1,93 @@
+use crate::grab::{HeldBy, Holding};
+use crate::machine::celeste::CelesteState;
+use crate::machine::postbox::PostboxState;
+use crate::stage::Stage;
+use crate::world::{fresh_stock, Allegiance, Fighter, HangingFrom, Position, StandingOn, Stocks};
+use bevy::log;
+use bevy::prelude::*;
+
+// Set in training mode, so that fighters never run out of stocks.
+// Never set in a match, so it isn't rolled back.
+#[derive(Resource, Default, Debug)]
+pub struct InfiniteStocks(pub bool);
+
+pub fn death_system(
+    mut commands: Commands,
+    infinite_stocks: Res<InfiniteStocks>,
+    stage: Res<Stage>,
+    mut query: Query<
+        (
+            &Allegiance,
+            &Position,
+            &mut Stocks,
+            (Option<&PostboxState>, Option<&CelesteState>),
+            Entity,
+        ),
+        With<Fighter>,
+    >,
+) {
+    for (allegiance, position, mut stocks, (postbox, celeste), entity) in query.iter_mut() {
+        if !stage.blast_zone.contains(position) {
+            log::debug!("Character dying");
+            if !infinite_stocks.0 {
+                stocks.count -= 1;
+            }
+            if stocks.count <= 0 {
+                commands.entity(entity).despawn();
+                log::debug!("Out of stocks, despawned");
+            } else {
+                // Nothing carries over from the last stock. Whoever was at
+                // the other end of a grab is let go of by the grab system.
+                let respawn = stage.respawn_point(allegiance.handle.0);
+                let mut fighter = commands.entity(entity);
+                fighter
+                    .insert(fresh_stock(&respawn))
+                    .remove::<(StandingOn, HangingFrom, Holding, HeldBy)>();
+                if postbox.is_some() {
+                    fighter.insert(PostboxState::default());
+                }
+                if celeste.is_some() {
+                    fighter.insert(CelesteState::default());
+                }
+                log::debug!("Down to {:?} stocks, respawning", stocks.count);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless;
+    use crate::shield::Shield;
+    use crate::world::{Damage, Hitstun};
+
+    #[test]
+    fn respawning_starts_the_stock_afresh() {
+        let mut app = headless::landed_app();
+        let entity = headless::fighter(&mut app, 0);
+        app.world.entity_mut(entity).insert((
+            Position { x: 100_000, y: 0 },
+            Damage { percent: 80 },
+            Hitstun { frames: 30 },
+            Shield {
+                health: 1,
+                ..default()
+            },
+        ));
+        headless::step(&mut app, 1, &[]);
+
+        let fighter = app.world.entity(entity);
+        assert_eq!(fighter.get::<Stocks>().unwrap().count, 3);
+        assert_eq!(fighter.get::<Damage>().unwrap().percent, 0);
+        assert_eq!(fighter.get::<Hitstun>().unwrap().frames, 0);
+        assert_eq!(
+            fighter.get::<Shield>().unwrap().health,
+            Shield::default().health
+        );
+        // The hitstun sent the fighter tumbling before it died
+        let state = fighter.get::<PostboxState>().unwrap();
+        assert_eq!(state.stance, PostboxState::default().stance);
+        assert_eq!(state.countdown, -1);
+    }
+}
@@ -0,0 +1,531 @@
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::log;
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use bevy_asset_loader::prelude::*;
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::input::Button;
+use crate::machine::celeste::Celeste;
+use crate::machine::postbox::Postbox;
+use crate::machine::types::{Armour, Physics};
+use crate::machine::{Character, StanceKind};
+use crate::world::Bounds;
+
+#[derive(AssetCollection, Resource)]
+pub struct FrameDataAssets {
+    #[asset(path = "framedata/postbox.yaml")]
+    pub postbox: Handle<FrameDataTable>,
+    #[asset(path = "framedata/celeste.yaml")]
+    pub celeste: Handle<FrameDataTable>,
+}
+
+// What a stance does on one particular frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FrameData {
+    pub physics: Physics,
+    pub armour: Armour,
+}
+
+// A range of frames, inclusive on both ends, counted from
+// the first frame of the stance (frame 0).
+#[derive(Copy, Clone, Debug, Default, Reflect, Deserialize, PartialEq, Eq, Hash)]
+pub struct FrameWindow(pub u8, pub u8);
+
+impl FrameWindow {
+    pub fn contains(&self, frame: u8) -> bool {
+        self.0 <= frame && frame <= self.1
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CancelWindow {
+    pub frames: FrameWindow,
+    // Indices of stances, see `StanceKind::index`.
+    pub into: Vec<usize>,
+}
+
+// A box which damages any fighter whose hurtbox it overlaps.
+// Its bounds are given for a fighter facing right.
+// Knockback is in pixels per frame, growing by `knockback_growth`
+// for every 100% of damage the defender has taken, and is sent
+// out at `angle` degrees, where 0 is away from the attacker and
+// 90 is straight up.
+#[derive(Copy, Clone, Debug, Default, Reflect, Deserialize, Hash)]
+pub struct Hitbox {
+    pub frames: FrameWindow,
+    #[serde(flatten)]
+    pub bounds: Bounds,
+    pub damage: u16,
+    pub base_knockback: i32,
+    pub knockback_growth: i32,
+    pub angle: i32,
+    #[serde(default)]
+    pub kind: HitboxKind,
+}
+
+// Frames a smash attack can be charged for. At full charge it
+// does 40% more damage and knockback.
+pub const FULL_CHARGE: u8 = 60;
+
+impl Hitbox {
+    pub fn charged(&self, frames: u8) -> Hitbox {
+        let frames = frames.min(FULL_CHARGE) as i32;
+        let boost = |value: i32| value + value * frames * 2 / (5 * FULL_CHARGE as i32);
+        Hitbox {
+            damage: boost(self.damage as i32) as u16,
+            base_knockback: boost(self.base_knockback),
+            knockback_growth: boost(self.knockback_growth),
+            ..*self
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Reflect, Deserialize, PartialEq, Eq, Hash)]
+pub enum HitboxKind {
+    #[default]
+    Strike,
+    // Grabs whoever it overlaps instead of hitting them, even
+    // through a shield. Its damage and knockback are unused.
+    Grab,
+    // Only hits the fighter the attacker is holding, doing damage
+    // without any knockback or hitstun.
+    Pummel,
+}
+
+// A projectile a stance throws out on one of its frames. Where it
+// starts and how fast it goes are given for a fighter facing right,
+// relative to the fighter. It lasts `lifetime` frames unless it hits
+// someone first, and its hitbox's frames count from when it was thrown.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectileSpawn {
+    pub frame: u8,
+    pub x: i32,
+    pub y: i32,
+    pub velocity_x: i32,
+    pub velocity_y: i32,
+    pub lifetime: u8,
+    pub hitbox: Hitbox,
+}
+
+#[derive(Clone, Debug)]
+pub struct StanceFrameData {
+    // Number of frames before the stance times out, or -1
+    // if the stance lasts until something interrupts it.
+    pub timeout: i8,
+    // Indexed by frame. Stances which never time out only
+    // carry a single entry.
+    pub frames: Vec<FrameData>,
+    pub cancels: Vec<CancelWindow>,
+    pub hitboxes: Vec<Hitbox>,
+    pub projectiles: Vec<ProjectileSpawn>,
+}
+
+impl StanceFrameData {
+    pub fn frame(&self, frame: u8) -> FrameData {
+        let idx = (frame as usize).min(self.frames.len() - 1);
+        self.frames[idx]
+    }
+
+    pub fn cancellable_into<S: StanceKind>(&self, frame: u8, stance: S) -> bool {
+        let idx = stance.index();
+        self.cancels
+            .iter()
+            .any(|cancel| cancel.frames.contains(frame) && cancel.into.contains(&idx))
+    }
+
+    pub fn active_hitboxes(&self, frame: u8) -> impl Iterator<Item = &Hitbox> {
+        self.hitboxes
+            .iter()
+            .filter(move |hitbox| hitbox.frames.contains(frame))
+    }
+}
+
+// Movement constants which hold across all of a character's stances.
+// Speeds are in pixels per frame.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Attributes {
+    pub walk_speed: i32,
+    pub crawl_speed: i32,
+    // Only characters which can roll need a roll speed.
+    #[serde(default)]
+    pub roll_speed: i32,
+    // Nor do characters which can't dash and run need these. Traction
+    // is how quickly a skidding fighter slows down.
+    #[serde(default)]
+    pub dash_speed: i32,
+    #[serde(default)]
+    pub run_speed: i32,
+    #[serde(default)]
+    pub traction: i32,
+    pub air_speed: i32,
+    pub jump_velocity: i32,
+    pub gravity: i32,
+    // Jumps which can be taken after leaving the ground.
+    pub max_aerial_jumps: u8,
+    // Frames a press of each button counts for, including the one it
+    // is made on, so that it isn't lost while the fighter can't act.
+    // Buttons left out only count on the frame they are pressed.
+    #[serde(default)]
+    pub buffer: HashMap<Button, u8>,
+}
+
+#[derive(Debug, TypeUuid, TypePath)]
+#[uuid = "4b4f5a3e-2f4d-4a57-9d1c-6f0e3f3b8c21"]
+pub struct FrameDataTable {
+    pub character: &'static str,
+    pub attributes: Attributes,
+    // Indexed by `StanceKind::index`.
+    stances: Vec<StanceFrameData>,
+}
+
+impl FrameDataTable {
+    pub fn get<S: StanceKind>(&self, stance: S) -> &StanceFrameData {
+        // Every stance is checked for at load time.
+        &self.stances[stance.index()]
+    }
+
+    pub fn from_yaml(bytes: &[u8]) -> Result<Self, FrameDataError> {
+        let raw: RawFrameDataTable =
+            serde_yaml::from_slice(bytes).map_err(FrameDataError::Parse)?;
+        match raw.character.as_str() {
+            Postbox::NAME => raw.validate::<Postbox>(),
+            Celeste::NAME => raw.validate::<Celeste>(),
+            _ => Err(FrameDataError::UnknownCharacter(raw.character)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FrameDataError {
+    Parse(serde_yaml::Error),
+    UnknownCharacter(String),
+    UnknownStance(String),
+    MissingStance(&'static str),
+    ZeroTimeout(&'static str),
+    WindowOutOfRange {
+        stance: &'static str,
+        window: FrameWindow,
+    },
+    UnknownCancel {
+        stance: &'static str,
+        into: String,
+    },
+    EmptyHitbox(&'static str),
+    BadProjectile(&'static str),
+}
+
+impl fmt::Display for FrameDataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use FrameDataError as E;
+        match self {
+            E::Parse(err) => write!(f, "could not parse frame data: {err}"),
+            E::UnknownCharacter(name) => write!(f, "unknown character `{name}`"),
+            E::UnknownStance(name) => write!(f, "unknown stance `{name}`"),
+            E::MissingStance(name) => write!(f, "no frame data for stance `{name}`"),
+            E::ZeroTimeout(name) => write!(
+                f,
+                "stance `{name}` has a timeout of 0, use -1 for stances that never time out"
+            ),
+            E::WindowOutOfRange { stance, window } => write!(
+                f,
+                "stance `{stance}` has a window over frames {}-{} which is outside of the stance",
+                window.0, window.1
+            ),
+            E::UnknownCancel { stance, into } => {
+                write!(f, "stance `{stance}` cancels into unknown stance `{into}`")
+            }
+            E::EmptyHitbox(name) => write!(f, "stance `{name}` has a hitbox with no area"),
+            E::BadProjectile(name) => write!(
+                f,
+                "stance `{name}` has a projectile which doesn't last, or whose hitbox \
+                 is out of its lifetime or isn't a strike"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FrameDataError {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFrameDataTable {
+    character: String,
+    attributes: Attributes,
+    stances: HashMap<String, RawStanceFrameData>,
+}
+
+impl RawFrameDataTable {
+    fn validate<C: Character>(mut self) -> Result<FrameDataTable, FrameDataError> {
+        for name in self.stances.keys() {
+            if C::Stance::from_name(name).is_none() {
+                return Err(FrameDataError::UnknownStance(name.clone()));
+            }
+        }
+        let mut stances = Vec::new();
+        for stance in C::Stance::ALL {
+            let raw_stance = self
+                .stances
+                .remove(stance.name())
+                .ok_or(FrameDataError::MissingStance(stance.name()))?;
+            stances.push(raw_stance.validate(*stance)?);
+        }
+        Ok(FrameDataTable {
+            character: C::NAME,
+            attributes: self.attributes,
+            stances,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawStanceFrameData {
+    timeout: i8,
+    armour: Armour,
+    physics: Physics,
+    #[serde(default)]
+    windows: Vec<RawWindow>,
+    #[serde(default)]
+    cancels: Vec<RawCancelWindow>,
+    #[serde(default)]
+    hitboxes: Vec<Hitbox>,
+    #[serde(default)]
+    projectiles: Vec<ProjectileSpawn>,
+}
+
+// Overrides the stance-wide armour or physics for some frames.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawWindow {
+    frames: FrameWindow,
+    armour: Option<Armour>,
+    physics: Option<Physics>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCancelWindow {
+    frames: FrameWindow,
+    into: Vec<String>,
+}
+
+impl RawStanceFrameData {
+    fn validate<S: StanceKind>(self, stance: S) -> Result<StanceFrameData, FrameDataError> {
+        let name = stance.name();
+        if self.timeout == 0 {
+            return Err(FrameDataError::ZeroTimeout(name));
+        }
+        // A stance that never times out is described by its first frame.
+        let len = if self.timeout < 0 { 1 } else { self.timeout };
+        let check_window = |window: FrameWindow| {
+            if window.0 > window.1 || window.1 as i16 >= len as i16 {
+                Err(FrameDataError::WindowOutOfRange {
+                    stance: name,
+                    window,
+                })
+            } else {
+                Ok(())
+            }
+        };
+        let mut frames = vec![
+            FrameData {
+                physics: self.physics,
+                armour: self.armour,
+            };
+            len as usize
+        ];
+        for window in self.windows {
+            check_window(window.frames)?;
+            for frame in &mut frames[window.frames.0 as usize..=window.frames.1 as usize] {
+                if let Some(armour) = window.armour {
+                    frame.armour = armour;
+                }
+                if let Some(physics) = window.physics {
+                    frame.physics = physics;
+                }
+            }
+        }
+        let mut cancels = Vec::new();
+        for cancel in self.cancels {
+            check_window(cancel.frames)?;
+            let mut into = Vec::new();
+            for target in cancel.into {
+                match S::from_name(&target) {
+                    Some(target) => into.push(target.index()),
+                    None => {
+                        return Err(FrameDataError::UnknownCancel {
+                            stance: name,
+                            into: target,
+                        })
+                    }
+                }
+            }
+            cancels.push(CancelWindow {
+                frames: cancel.frames,
+                into,
+            });
+        }
+        for hitbox in &self.hitboxes {
+            check_window(hitbox.frames)?;
+            if hitbox.bounds.width <= 0 || hitbox.bounds.height <= 0 {
+                return Err(FrameDataError::EmptyHitbox(name));
+            }
+        }
+        for projectile in &self.projectiles {
+            check_window(FrameWindow(projectile.frame, projectile.frame))?;
+            let hitbox = projectile.hitbox;
+            if projectile.lifetime == 0
+                || hitbox.frames.1 >= projectile.lifetime
+                || hitbox.frames.0 > hitbox.frames.1
+                || hitbox.kind != HitboxKind::Strike
+            {
+                return Err(FrameDataError::BadProjectile(name));
+            }
+            if hitbox.bounds.width <= 0 || hitbox.bounds.height <= 0 {
+                return Err(FrameDataError::EmptyHitbox(name));
+            }
+        }
+        log::trace!("Loaded frame data for {name}");
+        Ok(StanceFrameData {
+            timeout: self.timeout,
+            frames,
+            cancels,
+            hitboxes: self.hitboxes,
+            projectiles: self.projectiles,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct FrameDataLoader;
+
+impl AssetLoader for FrameDataLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let table = FrameDataTable::from_yaml(bytes).map_err(|err| {
+                log::error!("{:?}: {err}", load_context.path());
+                err
+            })?;
+            load_context.set_default_asset(LoadedAsset::new(table));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["yaml"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::celeste::{GroundedStance, Stance};
+
+    // Celeste's attributes, to be followed by some of her stances.
+    const CELESTE: &str = "
+character: celeste
+attributes:
+  walk_speed: 4
+  crawl_speed: 0
+  air_speed: 3
+  jump_velocity: 10
+  gravity: 1
+  max_aerial_jumps: 1
+stances:
+";
+
+    fn table_error(stances: &str) -> FrameDataError {
+        let yaml = format!("{CELESTE}{stances}");
+        FrameDataTable::from_yaml(yaml.as_bytes()).unwrap_err()
+    }
+
+    // Checks `yaml` as the frame data for Celeste's jab.
+    fn jab_error(yaml: &str) -> FrameDataError {
+        let raw: RawStanceFrameData = match serde_yaml::from_str(yaml) {
+            Ok(raw) => raw,
+            Err(err) => return FrameDataError::Parse(err),
+        };
+        raw.validate(Stance::Grounded(GroundedStance::Jabbing))
+            .unwrap_err()
+    }
+
+    #[test]
+    fn stances_the_character_lacks_are_rejected() {
+        let err = table_error("  flying: {armour: None, physics: Falling, timeout: -1}");
+        assert!(
+            matches!(&err, FrameDataError::UnknownStance(name) if name == "flying"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn every_stance_needs_frame_data() {
+        let err = table_error("  standing: {armour: None, physics: NotMoving, timeout: -1}");
+        assert!(
+            matches!(err, FrameDataError::MissingStance("walking")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn timeouts_of_zero_are_rejected() {
+        let err = jab_error("{armour: None, physics: NotMoving, timeout: 0}");
+        assert!(
+            matches!(err, FrameDataError::ZeroTimeout("jabbing")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn windows_must_fit_in_the_stance() {
+        let err = jab_error(
+            "{armour: None, physics: NotMoving, timeout: 18,
+              windows: [{frames: [10, 18], physics: Falling}]}",
+        );
+        assert!(
+            matches!(
+                err,
+                FrameDataError::WindowOutOfRange {
+                    stance: "jabbing",
+                    window: FrameWindow(10, 18),
+                }
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn cancels_must_go_into_known_stances() {
+        let err = jab_error(
+            "{armour: None, physics: NotMoving, timeout: 18,
+              cancels: [{frames: [10, 17], into: [standing, flying]}]}",
+        );
+        assert!(
+            matches!(
+                &err,
+                FrameDataError::UnknownCancel { stance: "jabbing", into } if into == "flying"
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn unknown_physics_fail_to_parse() {
+        let err = jab_error("{armour: None, physics: Flying, timeout: 18}");
+        assert!(matches!(err, FrameDataError::Parse(_)), "{err}");
+        assert!(
+            err.to_string().contains("unknown variant `Flying`"),
+            "{err}"
+        );
+    }
+}
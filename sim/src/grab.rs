@@ -0,0 +1,335 @@
+use crate::input::Button;
+use crate::machine::types::Physics;
+use crate::physics::move_horizontally;
+use crate::types::PlayerId;
+use crate::world::{
+    Allegiance, Bounds, ButtonDiff, Fighter, InputDiff, Orientation, Platform, Position,
+    StandingOn, Velocity, FIGHTER_DIMENSIONS,
+};
+use bevy::log;
+use bevy::prelude::*;
+use strum::IntoEnumIterator;
+
+// Frames a held fighter at 0% takes to break free without mashing.
+const ESCAPE_FRAMES: i16 = 60;
+// Frames taken off the hold for every button the held fighter presses.
+pub const MASH_FRAMES: i16 = 6;
+
+// Fighters are held for a frame longer for every 2% of damage they have.
+pub fn escape_frames(percent: u16) -> i16 {
+    ESCAPE_FRAMES.saturating_add((percent / 2) as i16)
+}
+
+// The two ends of a grab point at each other by player handle,
+// which stays the same across rollbacks where entities may not.
+#[derive(Component, Default, Reflect, Debug, Hash, Clone)]
+pub struct Holding {
+    pub victim: PlayerId,
+}
+
+// `escape` counts down to the held fighter breaking free.
+#[derive(Component, Default, Reflect, Debug, Hash, Clone)]
+pub struct HeldBy {
+    pub holder: PlayerId,
+    pub escape: i16,
+}
+
+type GrabFighterQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        (&'static Allegiance, &'static Physics, &'static InputDiff),
+        (
+            &'static mut Position,
+            &'static mut Velocity,
+            &'static mut Orientation,
+        ),
+        (
+            Option<&'static Holding>,
+            Option<&'static mut HeldBy>,
+            Option<&'static StandingOn>,
+        ),
+    ),
+    With<Fighter>,
+>;
+
+fn facing(orientation: Orientation) -> Orientation {
+    match orientation {
+        Orientation::Left => Orientation::Right,
+        Orientation::Right => Orientation::Left,
+    }
+}
+
+// Held fighters are carried just in front of whoever holds them,
+// facing them, until they mash free or either fighter's stance moves
+// on from the grab, such as by a throw landing or a hit from someone
+// else. Either way both ends of the grab are let go of together.
+pub fn grab_system(
+    mut commands: Commands,
+    mut fighter_query: GrabFighterQuery,
+    platform_query: Query<&Platform>,
+) {
+    log::debug!("grab system beginning");
+    let walls: Vec<Bounds> = platform_query
+        .iter()
+        .filter_map(Platform::solid_bounds)
+        .collect();
+    let holders: Vec<_> = fighter_query
+        .iter()
+        .filter(|(_, _, _, (holding, _, _))| holding.is_some())
+        .map(
+            |(
+                entity,
+                (allegiance, physics, _),
+                (position, _, orientation),
+                (_, _, standing_on),
+            )| {
+                (
+                    allegiance.handle,
+                    entity,
+                    *physics,
+                    (position.clone(), *orientation, standing_on.cloned()),
+                )
+            },
+        )
+        .collect();
+    let mut still_holding = Vec::new();
+
+    for (
+        entity,
+        (_, physics, input),
+        (mut position, mut velocity, mut orientation),
+        (_, held_by, _),
+    ) in fighter_query.iter_mut()
+    {
+        let Some(mut held_by) = held_by else {
+            continue;
+        };
+        let presses = Button::iter()
+            .filter(|button| input.get(*button) == ButtonDiff::Pressed)
+            .count() as i16;
+        held_by.escape -= 1 + presses * MASH_FRAMES;
+        let holder = holders
+            .iter()
+            .find(|(handle, _, _, _)| *handle == held_by.holder);
+        match holder {
+            Some((
+                handle,
+                _,
+                Physics::Holding,
+                (holder_position, holder_orientation, holder_standing_on),
+            )) if *physics == Physics::Held && held_by.escape > 0 => {
+                // Carried out from the holder as if walked there, so
+                // that a wall in the way holds the fighter back.
+                let offset = match holder_orientation {
+                    Orientation::Right => FIGHTER_DIMENSIONS,
+                    Orientation::Left => -FIGHTER_DIMENSIONS,
+                };
+                *position = holder_position.clone();
+                move_horizontally(&mut position, &mut Velocity { x: offset, y: 0 }, &walls);
+                *velocity = Velocity { x: 0, y: 0 };
+                *orientation = facing(*holder_orientation);
+                // Standing on whatever the holder is, so as to still be
+                // on their feet when let go.
+                match holder_standing_on {
+                    Some(standing_on) => commands.entity(entity).insert(standing_on.clone()),
+                    None => commands.entity(entity).remove::<StandingOn>(),
+                };
+                still_holding.push(*handle);
+            }
+            _ => {
+                log::trace!("Letting go of {:?}", entity);
+                commands.entity(entity).remove::<HeldBy>();
+            }
+        }
+    }
+
+    for (handle, entity, _, _) in holders {
+        if !still_holding.contains(&handle) {
+            commands.entity(entity).remove::<Holding>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless;
+    use crate::machine::postbox::{AerialStance, GroundedStance, Stance};
+    use crate::world::{fighter_body, Damage, PlatformId, PlatformKind};
+
+    // Stands both fighters on Battlefield's main platform, the first
+    // facing the second from within grabbing range.
+    fn face_off(app: &mut App) {
+        headless::stand(app, 0, 0, Orientation::Right);
+        headless::stand(app, 1, 40, Orientation::Left);
+    }
+
+    fn stance(app: &mut App, handle: usize) -> Stance {
+        headless::postbox_state(app, handle).stance
+    }
+
+    fn is_held(app: &mut App) -> bool {
+        let holder = headless::fighter(app, 0);
+        let victim = headless::fighter(app, 1);
+        match (
+            app.world.get::<Holding>(holder),
+            app.world.get::<HeldBy>(victim),
+        ) {
+            (Some(holding), Some(held_by)) => {
+                holding.victim == PlayerId(1) && held_by.holder == PlayerId(0)
+            }
+            (None, None) => false,
+            _ => panic!("only one end of the grab is tethered"),
+        }
+    }
+
+    fn percent(app: &mut App, handle: usize) -> u16 {
+        let entity = headless::fighter(app, handle);
+        app.world.get::<Damage>(entity).unwrap().percent
+    }
+
+    // Lets the fighters land, then has the first grab the second
+    // out of its shield.
+    fn grab(app: &mut App) {
+        headless::step(app, 60, &["-", "-"]);
+        face_off(app);
+        headless::step(app, 1, &["S", "S"]);
+        headless::step(app, 1, &["SH", "S"]);
+        headless::step(app, 12, &["-", "S"]);
+    }
+
+    #[test]
+    fn grabs_go_through_shields() {
+        let mut app = headless::test_app();
+        grab(&mut app);
+
+        assert!(is_held(&mut app));
+        assert_eq!(
+            stance(&mut app, 0),
+            Stance::Grounded(GroundedStance::Holding)
+        );
+        assert_eq!(stance(&mut app, 1), Stance::Grounded(GroundedStance::Held));
+        let victim = headless::fighter(&mut app, 1);
+        assert_eq!(
+            *app.world.get::<Position>(victim).unwrap(),
+            Position {
+                x: FIGHTER_DIMENSIONS,
+                y: 1
+            }
+        );
+    }
+
+    #[test]
+    fn held_fighters_are_not_carried_into_walls() {
+        let mut app = headless::test_app();
+        grab(&mut app);
+        // A wall standing on the stage, just past where the held
+        // fighter would otherwise be carried to
+        let wall = Platform {
+            x: 60,
+            y: 80,
+            width: 40,
+            depth: 80,
+            kind: PlatformKind::Solid,
+            id: PlatformId(4),
+        };
+        let wall_bounds = wall.solid_bounds().unwrap();
+        app.world.spawn(wall);
+        headless::step(&mut app, 1, &["-", "-"]);
+
+        assert!(is_held(&mut app));
+        let victim = headless::fighter(&mut app, 1);
+        let position = app.world.get::<Position>(victim).unwrap();
+        assert_eq!(position.x, wall_bounds.x - FIGHTER_DIMENSIONS);
+        assert!(!fighter_body(position).overlaps(&wall_bounds));
+    }
+
+    #[test]
+    fn pummels_do_damage_without_letting_go() {
+        let mut app = headless::test_app();
+        grab(&mut app);
+        headless::step(&mut app, 1, &["H", "-"]);
+        headless::step(&mut app, 20, &["-", "-"]);
+
+        assert!(is_held(&mut app));
+        assert!(percent(&mut app, 1) > 0);
+        assert_eq!(
+            stance(&mut app, 0),
+            Stance::Grounded(GroundedStance::Holding)
+        );
+    }
+
+    #[test]
+    fn throws_launch_the_held_fighter() {
+        for (letter, forwards) in [("R", true), ("L", false)] {
+            let mut app = headless::test_app();
+            grab(&mut app);
+            headless::step(&mut app, 1, &[letter, "-"]);
+            headless::step(&mut app, 12, &["-", "-"]);
+
+            assert!(!is_held(&mut app));
+            assert!(percent(&mut app, 1) > 0);
+            assert_eq!(stance(&mut app, 1), Stance::Aerial(AerialStance::Tumbling));
+            let victim = headless::fighter(&mut app, 1);
+            let velocity = app.world.get::<Velocity>(victim).unwrap();
+            assert_eq!(velocity.x > 0, forwards, "thrown with {letter}");
+        }
+    }
+
+    #[test]
+    fn mashing_breaks_free_sooner() {
+        let frames_held = |mash: bool| {
+            let mut app = headless::test_app();
+            grab(&mut app);
+            let mut frames = 0;
+            while is_held(&mut app) {
+                let letters = if mash && frames % 2 == 0 { "J" } else { "-" };
+                headless::step(&mut app, 1, &["-", letters]);
+                frames += 1;
+            }
+            // Both stay on their feet once let go
+            let victim = headless::fighter(&mut app, 1);
+            for _ in 0..10 {
+                headless::step(&mut app, 1, &["-", "-"]);
+                assert_eq!(
+                    stance(&mut app, 0),
+                    Stance::Grounded(GroundedStance::Standing)
+                );
+                assert_eq!(
+                    stance(&mut app, 1),
+                    Stance::Grounded(GroundedStance::Standing)
+                );
+                assert_eq!(app.world.get::<Position>(victim).unwrap().y, 1);
+            }
+            frames
+        };
+
+        assert!(frames_held(true) < frames_held(false));
+    }
+
+    #[test]
+    fn grabs_come_out_of_dashes_and_runs() {
+        for frames in [1, 14] {
+            let mut app = headless::test_app();
+            headless::step(&mut app, 60, &["-", "-"]);
+            headless::stand(&mut app, 0, -250, Orientation::Right);
+            headless::step(&mut app, frames, &["RF", "-"]);
+            headless::step(&mut app, 1, &["RFSH", "-"]);
+
+            assert_eq!(
+                stance(&mut app, 0),
+                Stance::Grounded(GroundedStance::Grabbing),
+                "grabbing after {frames} frames on the move"
+            );
+        }
+    }
+
+    #[test]
+    fn fighters_are_held_longer_at_higher_percents() {
+        assert_eq!(escape_frames(0), ESCAPE_FRAMES);
+        assert!(escape_frames(150) > escape_frames(50));
+        assert!(escape_frames(u16::MAX) > 0);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::framedata::{FrameDataAssets, FrameDataTable};
+use crate::input::{CombinedInput, InputSource};
+use crate::replay::{Replay, ReplayPlayer};
+use crate::stage::{Stage, StageAssets, StageId};
+use crate::synctest::{verify_system, SyncTestLog};
+use crate::types::{GgrsConfig, PlayerId};
+use crate::world::{spawn_fighter, spawn_stage, CharacterId, FrameCount};
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_ggrs::{GgrsSchedule, Session};
+use ggrs::{PlayerHandle, SessionBuilder};
+
+use std::time::Duration;
+
+// The simulation's frame data and stages, built into the library so
+// that matches can be run without an asset folder.
+const POSTBOX_FRAME_DATA: &[u8] = include_bytes!("../assets/framedata/postbox.yaml");
+const CELESTE_FRAME_DATA: &[u8] = include_bytes!("../assets/framedata/celeste.yaml");
+const PLATFORM_STAGE: &[u8] = include_bytes!("../assets/stages/platform.stage.yaml");
+const BATTLEFIELD_STAGE: &[u8] = include_bytes!("../assets/stages/battlefield.stage.yaml");
+
+fn load_frame_data(world: &mut World) {
+    let mut tables = world.resource_mut::<Assets<FrameDataTable>>();
+    let mut load = |yaml| tables.add(FrameDataTable::from_yaml(yaml).unwrap());
+    let frame_data = FrameDataAssets {
+        postbox: load(POSTBOX_FRAME_DATA),
+        celeste: load(CELESTE_FRAME_DATA),
+    };
+    world.insert_resource(frame_data);
+}
+
+fn load_stages(world: &mut World) {
+    let mut stages = world.resource_mut::<Assets<Stage>>();
+    let mut load = |yaml| stages.add(Stage::from_yaml(yaml).unwrap());
+    let stage_assets = StageAssets {
+        platform: load(PLATFORM_STAGE),
+        battlefield: load(BATTLEFIELD_STAGE),
+    };
+    world.insert_resource(stage_assets);
+}
+
+fn source_input_system(
+    In(handle): In<PlayerHandle>,
+    mut source: ResMut<InputSource>,
+) -> CombinedInput {
+    source.next(handle).unwrap_or_else(CombinedInput::new)
+}
+
+// A match with no window, audio or assets on disk. Every update
+// advances the simulation by one frame's worth of time, so GGRS
+// simulates one frame per update.
+pub fn headless_app(
+    characters: &[CharacterId],
+    stage: StageId,
+    inputs: InputSource,
+    session: Option<Session<GgrsConfig>>,
+) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(
+            Duration::from_secs(1) / crate::FPS as u32,
+        ))
+        .insert_resource(inputs);
+    crate::add_simulation(&mut app, source_input_system);
+    load_frame_data(&mut app.world);
+    load_stages(&mut app.world);
+
+    if let Some(session) = session {
+        app.insert_resource(session);
+    }
+    let characters = characters.to_vec();
+    app.add_systems(
+        Startup,
+        move |mut commands: Commands,
+              stage_assets: Res<StageAssets>,
+              stages: Res<Assets<Stage>>| {
+            let stage = stages.get(stage_assets.get(stage)).unwrap();
+            spawn_stage(&mut commands, stage);
+            for (handle, character) in characters.iter().enumerate() {
+                spawn_fighter(&mut commands, stage, PlayerId(handle), *character);
+            }
+        },
+    );
+    app
+}
+
+// A headless match under a sync test, which panics as soon as
+// a frame resimulates differently.
+pub fn synctest_app(
+    characters: &[CharacterId],
+    stage: StageId,
+    inputs: InputSource,
+    check_distance: usize,
+) -> App {
+    let sess = SessionBuilder::<GgrsConfig>::new()
+        .with_num_players(characters.len())
+        .with_check_distance(check_distance)
+        .start_synctest_session()
+        .expect("a sync test session can always be started");
+    let mut app = headless_app(characters, stage, inputs, Some(Session::SyncTest(sess)));
+    app.init_resource::<SyncTestLog>().add_systems(
+        GgrsSchedule,
+        verify_system.after(crate::checksum::checksum_system),
+    );
+    app
+}
+
+// Plays a replay back with no checks, as fast as the app is updated.
+pub fn replay_app(replay: &Replay) -> App {
+    let sess = SessionBuilder::<GgrsConfig>::new()
+        .with_num_players(replay.characters.len())
+        .with_check_distance(0)
+        .start_synctest_session()
+        .expect("a sync test session can always be started");
+    headless_app(
+        &replay.characters,
+        replay.stage,
+        InputSource::script(replay.inputs.clone()),
+        Some(Session::SyncTest(sess)),
+    )
+}
+
+// A replay which is only stepped through by its ReplayPlayer, as in
+// the client's replay viewer. The fighters are spawned by the first update.
+pub fn viewer_app(replay: &Replay) -> App {
+    let mut app = headless_app(&replay.characters, replay.stage, InputSource::Device, None);
+    app.insert_resource(ReplayPlayer::new(replay.clone()));
+    app
+}
+
+// Updates the app until it has simulated `frames` frames.
+pub fn run_frames(app: &mut App, frames: u32) {
+    // GGRS runs about one frame per update, this leaves plenty of slack
+    for _ in 0..frames * 2 {
+        if app.world.resource::<FrameCount>().0 >= frames {
+            return;
+        }
+        app.update();
+    }
+    panic!(
+        "only simulated {} of {frames} frames",
+        app.world.resource::<FrameCount>().0
+    );
+}
+
+// Fixtures for the simulation's own tests. Their matches are always
+// two Postboxes, the first starting on the left of the stage.
+#[cfg(test)]
+pub(crate) const TEST_FIGHTERS: [CharacterId; 2] = [CharacterId::Postbox, CharacterId::Postbox];
+
+// A match on Battlefield which is stepped by hand with `step` rather
+// than through GGRS. The fighters are spawned, in the air, by the
+// time it is returned.
+#[cfg(test)]
+pub fn test_app() -> App {
+    let mut app = headless_app(
+        &TEST_FIGHTERS,
+        StageId::Battlefield,
+        InputSource::Device,
+        None,
+    );
+    app.update();
+    app
+}
+
+// A test app whose fighters have dropped on to the stage and
+// are standing still.
+#[cfg(test)]
+pub fn landed_app() -> App {
+    let mut app = test_app();
+    step(&mut app, 30, &[]);
+    app
+}
+
+// A match on `stage` under a sync test, played from `script` (see
+// `InputSource::parse_script`) for `frames` frames.
+#[cfg(test)]
+pub fn scripted_app(stage: StageId, script: &str, frames: u32) -> App {
+    let inputs = InputSource::parse_script(script).unwrap();
+    let mut app = synctest_app(&TEST_FIGHTERS, stage, inputs, 2);
+    run_frames(&mut app, frames);
+    app
+}
+
+// Steps a test app with each player pressing the letters given for
+// them (see `CombinedInput::from_letters`), and the rest nothing.
+#[cfg(test)]
+pub fn step(app: &mut App, frames: u32, letters: &[&str]) {
+    let mut inputs = vec![CombinedInput::new(); TEST_FIGHTERS.len()];
+    for (input, letters) in inputs.iter_mut().zip(letters) {
+        *input = CombinedInput::from_letters(letters).unwrap();
+    }
+    for _ in 0..frames {
+        crate::step_frame(&mut app.world, inputs.clone());
+    }
+}
+
+// Steps a test app through runs of frames with the first player
+// pressing the letters given for each run.
+#[cfg(test)]
+pub fn play(app: &mut App, runs: &[(u32, &str)]) {
+    for (frames, letters) in runs {
+        step(app, *frames, &[letters]);
+    }
+}
+
+#[cfg(test)]
+pub fn fighter(app: &mut App, handle: usize) -> Entity {
+    let mut query = app
+        .world
+        .query_filtered::<(Entity, &crate::world::Allegiance), With<crate::world::Fighter>>();
+    query
+        .iter(&app.world)
+        .find(|(_, allegiance)| allegiance.handle.0 == handle)
+        .map(|(entity, _)| entity)
+        .unwrap()
+}
+
+#[cfg(test)]
+pub fn postbox_state(app: &mut App, handle: usize) -> crate::machine::postbox::PostboxState {
+    let entity = fighter(app, handle);
+    app.world
+        .get::<crate::machine::postbox::PostboxState>(entity)
+        .unwrap()
+        .clone()
+}
+
+// Stands a fighter still on Battlefield's main platform at `x`.
+#[cfg(test)]
+pub fn stand(app: &mut App, handle: usize, x: i32, facing: crate::world::Orientation) {
+    use crate::world::{Platform, PlatformKind, Position, StandingOn, Velocity};
+    let mut query = app.world.query::<&Platform>();
+    let stage = query
+        .iter(&app.world)
+        .find(|platform| platform.kind == PlatformKind::Solid)
+        .unwrap()
+        .id;
+    let entity = fighter(app, handle);
+    app.world.entity_mut(entity).insert((
+        Position { x, y: 1 },
+        Velocity { x: 0, y: 0 },
+        facing,
+        StandingOn { platform: stage },
+    ));
+}
@@ -0,0 +1,264 @@
+use bevy::input::ButtonState;
+use bevy::log;
+use bevy::prelude::*;
+use bytemuck::{Pod, Zeroable};
+use ggrs::PlayerHandle;
+use serde::{Deserialize, Serialize};
+
+use strum_macros::EnumIter;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumIter, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum Button {
+    Jump,
+    Hit,
+    Left,
+    Right,
+    Down,
+    Up,
+    Shield,
+    Special,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum InputState {
+    NotActivated = 0,
+    Activated = 1,
+}
+
+fn to_input_state(n: u8) -> InputState {
+    match n {
+        0 => InputState::NotActivated,
+        1 => InputState::Activated,
+        _ => panic!("Invalid InputState representation"),
+    }
+}
+
+const BITS_PER_INPUT: u8 = 1;
+
+const fn get_shift(input: Button) -> u8 {
+    BITS_PER_INPUT * input as u8
+}
+
+const BASE_MASK: u8 = 1;
+
+const fn shift_mask(input: Button) -> u8 {
+    BASE_MASK << get_shift(input)
+}
+
+fn shift_flag(input: Button, diff: InputState) -> u8 {
+    let flag = diff as u8;
+    let shift = get_shift(input);
+    log::trace!("input::shift_flag: shifting {:?} left by {:?}", diff, shift);
+    flag << shift
+}
+
+// How far the stick can be pushed along each axis, with up and right
+// positive. Positions are quantised to whole steps so that they are
+// small to send and the same on every machine.
+pub const STICK_MAX: i8 = 127;
+// How far a key or other digital direction pushes the stick: enough
+// to walk or tilt, but not to count as a flick. Directions are only
+// pushed all the way while the player holds their smash modifier.
+pub const STICK_TILT: i8 = 80;
+
+// The buttons one player is holding on one frame, and where their stick
+// is. Each player's input is sent separately, whether they are on this
+// machine or another, so it is kept to three bytes.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub struct CombinedInput {
+    buttons: u8,
+    stick_x: i8,
+    stick_y: i8,
+}
+
+// Where the stick is when only one of a pair of opposite
+// directions is held, pushing it `reach` from the middle.
+fn direction_axis(negative: bool, positive: bool, reach: i8) -> i8 {
+    match (negative, positive) {
+        (true, false) => -reach,
+        (false, true) => reach,
+        _ => 0,
+    }
+}
+
+// Special is the last button, and uses the last bit.
+pub const BUTTONS: usize = Button::Special as usize + 1;
+const ALL_BUTTONS: u8 = u8::MAX >> (u8::BITS as u8 - BITS_PER_INPUT * BUTTONS as u8);
+
+impl CombinedInput {
+    pub fn new() -> Self {
+        CombinedInput {
+            buttons: 0,
+            stick_x: 0,
+            stick_y: 0,
+        }
+    }
+
+    pub fn from_letters(letters: &str) -> Option<Self> {
+        let mut input = CombinedInput::new();
+        if letters == "-" {
+            return Some(input);
+        }
+        let mut reach = STICK_TILT;
+        for letter in letters.chars() {
+            let button = match letter.to_ascii_uppercase() {
+                'F' => {
+                    reach = STICK_MAX;
+                    continue;
+                }
+                'J' => Button::Jump,
+                'H' => Button::Hit,
+                'L' => Button::Left,
+                'R' => Button::Right,
+                'D' => Button::Down,
+                'U' => Button::Up,
+                'S' => Button::Shield,
+                'B' => Button::Special,
+                _ => return None,
+            };
+            input.set(button, ButtonState::Pressed);
+        }
+        // Directions are pushed as far as keys on a keyboard push them
+        let held = |button| input.get(button) == InputState::Activated;
+        input.set_stick(
+            direction_axis(held(Button::Left), held(Button::Right), reach),
+            direction_axis(held(Button::Down), held(Button::Up), reach),
+        );
+        Some(input)
+    }
+
+    pub fn stick(&self) -> (i8, i8) {
+        (self.stick_x, self.stick_y)
+    }
+
+    // Both axes are kept within STICK_MAX of the middle, so
+    // that either way along one goes just as far.
+    pub fn set_stick(&mut self, x: i8, y: i8) {
+        self.stick_x = x.max(-STICK_MAX);
+        self.stick_y = y.max(-STICK_MAX);
+    }
+
+    pub fn get(&self, button: Button) -> InputState {
+        let shift = get_shift(button);
+        let mask = shift_mask(button);
+        let flag = (self.buttons & mask) >> shift;
+        to_input_state(flag)
+    }
+
+    pub fn set(&mut self, button: Button, state: ButtonState) {
+        let next_state = match state {
+            ButtonState::Pressed => InputState::Activated,
+            ButtonState::Released => InputState::NotActivated,
+        };
+        let mask = shift_mask(button);
+        let flag = shift_flag(button, next_state);
+        self.buttons = (self.buttons & !mask) | (flag & mask);
+    }
+}
+
+// Where the local players' inputs come from. Anything but the
+// player's own controls is only used when testing rollback.
+#[derive(Resource, Debug, Clone)]
+pub enum InputSource {
+    // Read by the client from whatever the player is holding.
+    Device,
+    // A new random input every frame, from a xorshift generator.
+    Random {
+        state: u32,
+    },
+    // One input per player per frame. Once the script runs out
+    // the players stop pressing anything.
+    Script {
+        frames: Vec<Vec<CombinedInput>>,
+        next: Vec<usize>,
+    },
+}
+
+impl InputSource {
+    pub fn random(seed: u32) -> Self {
+        // xorshift never leaves a state of 0
+        InputSource::Random { state: seed.max(1) }
+    }
+
+    pub fn script(frames: Vec<Vec<CombinedInput>>) -> Self {
+        let players = frames.first().map_or(0, Vec::len);
+        InputSource::Script {
+            frames,
+            next: vec![0; players],
+        }
+    }
+
+    // Reads a script with one line per run of frames, such as
+    //
+    //   10 R -
+    //   1  RH J
+    //
+    // which holds right for player 0 over 10 frames, then hits while
+    // player 1 jumps. Buttons are given by the letters J(ump), H(it),
+    // L(eft), R(ight), D(own), U(p), S(hield) and B for special, and `-`
+    // is no buttons. Directions push the stick as far as a tilt, unless
+    // F is given too, which flicks it all the way over as for a smash.
+    pub fn parse_script(text: &str) -> Result<Self, String> {
+        let mut frames = Vec::new();
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let count: usize = words
+                .next()
+                .unwrap()
+                .parse()
+                .map_err(|_| format!("line {}: expected a frame count", lineno + 1))?;
+            let inputs = words
+                .map(|word| {
+                    CombinedInput::from_letters(word).ok_or(format!(
+                        "line {}: `{word}` is not a set of buttons",
+                        lineno + 1
+                    ))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            frames.extend(std::iter::repeat(inputs).take(count));
+        }
+        Ok(Self::script(frames))
+    }
+
+    // The next input for the player, or None if it has to be read
+    // from the player's device.
+    pub fn next(&mut self, handle: PlayerHandle) -> Option<CombinedInput> {
+        match self {
+            InputSource::Device => None,
+            InputSource::Random { state } => {
+                *state ^= *state << 13;
+                *state ^= *state >> 17;
+                *state ^= *state << 5;
+                let mut input = CombinedInput::new();
+                input.buttons = *state as u8 & ALL_BUTTONS;
+                input.set_stick((*state >> 8) as i8, (*state >> 16) as i8);
+                Some(input)
+            }
+            InputSource::Script { frames, next } => {
+                // Players the script has no column for press nothing
+                if handle >= next.len() {
+                    next.resize(handle + 1, 0);
+                }
+                let frame = next[handle];
+                next[handle] += 1;
+                Some(
+                    frames
+                        .get(frame)
+                        .and_then(|inputs| inputs.get(handle))
+                        .copied()
+                        .unwrap_or_else(CombinedInput::new),
+                )
+            }
+        }
+    }
+}
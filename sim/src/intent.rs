@@ -0,0 +1,349 @@
+use crate::input::{Button, CombinedInput, InputState};
+use crate::types::*;
+use crate::world::{is_being_pressed, Allegiance, ButtonDiff, InputBuffer, InputDiff, Stick};
+
+use bevy::log;
+use bevy::prelude::*;
+use bevy_ggrs::PlayerInputs;
+use strum::IntoEnumIterator;
+
+use std::collections::HashMap;
+
+fn to_button_diff(n: u16) -> ButtonDiff {
+    let res = match n {
+        0 => ButtonDiff::NotHeld,
+        1 => ButtonDiff::Held,
+        2 => ButtonDiff::Released,
+        3 => ButtonDiff::Pressed,
+        _ => panic!(),
+    };
+    log::trace!("input::to_button_diff: Converting {:?} to {:?}", n, res);
+    res
+}
+
+const BITS_PER_INPUT: u16 = 2;
+
+const fn get_shift(input: Button) -> u16 {
+    BITS_PER_INPUT * input as u16
+}
+
+const BASE_MASK: u16 = 3;
+
+fn shift_mask(input: Button) -> u16 {
+    BASE_MASK << get_shift(input)
+}
+
+fn shift_flag(input: Button, diff: ButtonDiff) -> u16 {
+    let flag = diff as u16;
+    let shift = get_shift(input);
+    log::trace!("input::shift_flag: shifting {:?} left by {:?}", diff, shift);
+    let res = flag << shift;
+    log::trace!(
+        "input::shift_flag: {:?} at {:?} is represented as {:?}",
+        input,
+        diff,
+        res
+    );
+    res
+}
+
+impl InputDiff {
+    pub fn new() -> Self {
+        InputDiff {
+            buttons: 0,
+            stick: Stick::default(),
+        }
+    }
+
+    pub fn get(self, button: Button) -> ButtonDiff {
+        let shift = get_shift(button);
+        let mask = shift_mask(button);
+        let masked_val = self.buttons & mask;
+        let flag = masked_val >> shift;
+        log::trace!(
+            "InputDiff::get: masking {:?} to get {:?} then shifting right by {:?} to get {:?}",
+            self.buttons,
+            masked_val,
+            shift,
+            flag
+        );
+        to_button_diff(flag)
+    }
+
+    pub fn set(&mut self, button: Button, state: InputState) {
+        let next_diff = if self.is_being_pressed(button) {
+            match state {
+                InputState::Activated => ButtonDiff::Held,
+                InputState::NotActivated => ButtonDiff::Released,
+            }
+        } else {
+            match state {
+                InputState::Activated => ButtonDiff::Pressed,
+                InputState::NotActivated => ButtonDiff::NotHeld,
+            }
+        };
+        let mask = shift_mask(button);
+        let flag = shift_flag(button, next_diff);
+        self.buttons = (self.buttons & !mask) | (flag & mask);
+    }
+
+    pub fn is_being_pressed(self, button: Button) -> bool {
+        let diff = self.get(button);
+        is_being_pressed(diff)
+    }
+
+    // Makes it look as though `button` was pressed this frame.
+    pub fn press(&mut self, button: Button) {
+        let mask = shift_mask(button);
+        let flag = shift_flag(button, ButtonDiff::Pressed);
+        self.buttons = (self.buttons & !mask) | (flag & mask);
+    }
+}
+
+impl InputBuffer {
+    // Remembers this frame's presses for as long as `windows` says,
+    // and counts down the ones made before.
+    pub fn update(&mut self, input: InputDiff, windows: &HashMap<Button, u8>) {
+        for button in Button::iter() {
+            let frames_left = &mut self.frames_left[button as usize];
+            *frames_left = if input.get(button) == ButtonDiff::Pressed {
+                windows.get(&button).copied().unwrap_or(1)
+            } else {
+                frames_left.saturating_sub(1)
+            };
+        }
+    }
+
+    // The input with every press which still counts made again.
+    pub fn apply(&self, mut input: InputDiff) -> InputDiff {
+        for button in Button::iter() {
+            if self.frames_left[button as usize] > 0 {
+                input.press(button);
+            }
+        }
+        input
+    }
+
+    // Forgets every press, once one of them has been acted on.
+    pub fn clear(&mut self) {
+        self.frames_left = Default::default();
+    }
+}
+
+// Within this far of the middle the stick counts as let go.
+pub const STICK_NEUTRAL: u8 = 30;
+// Past this far the stick is pushed hard enough for a smash attack,
+// if it got there quickly enough.
+pub const STICK_SMASH: u8 = 90;
+// Frames the stick has from leaving the middle to a smash attack
+// being started, for it to count as a flick rather than a tilt.
+pub const FLICK_FRAMES: u8 = 4;
+
+impl Stick {
+    pub fn update(&mut self, (x, y): (i8, i8)) {
+        // Swapping straight from one side to the other
+        // counts as leaving the middle again.
+        let travel = |axis: i8, last: i8, travel: u8| {
+            if axis.unsigned_abs() <= STICK_NEUTRAL {
+                0
+            } else if axis.signum() != last.signum() {
+                1
+            } else {
+                travel.saturating_add(1)
+            }
+        };
+        self.x_travel = travel(x, self.x, self.x_travel);
+        self.y_travel = travel(y, self.y, self.y_travel);
+        self.x = x;
+        self.y = y;
+    }
+
+    // The direction the stick has just been flicked in, if it has
+    // been pushed hard from the middle within the last few frames.
+    // Flicks up or down win over flicks to the side.
+    pub fn flick(&self) -> Option<Button> {
+        let flicked =
+            |axis: i8, travel: u8| axis.unsigned_abs() >= STICK_SMASH && travel <= FLICK_FRAMES;
+        if flicked(self.y, self.y_travel) {
+            Some(if self.y > 0 { Button::Up } else { Button::Down })
+        } else if flicked(self.x, self.x_travel) {
+            Some(if self.x > 0 {
+                Button::Right
+            } else {
+                Button::Left
+            })
+        } else {
+            None
+        }
+    }
+
+    // How far the stick is pushed to the side, out of STICK_MAX.
+    pub fn tilt_x(&self) -> u8 {
+        self.x.unsigned_abs()
+    }
+}
+
+fn mk_diff(old_diff: ButtonDiff, new_input: InputState) -> ButtonDiff {
+    match (is_being_pressed(old_diff), new_input) {
+        (true, InputState::Activated) => ButtonDiff::Held,
+        (true, InputState::NotActivated) => ButtonDiff::Released,
+        (false, InputState::Activated) => ButtonDiff::Pressed,
+        (false, InputState::NotActivated) => ButtonDiff::NotHeld,
+    }
+}
+
+// Every player's input for the frame being simulated, by player handle.
+// GGRS fills this in, except when a replay is stepped through by hand.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct FrameInputs(pub Vec<CombinedInput>);
+
+pub fn frame_inputs_system(
+    ggrs_inputs: Res<PlayerInputs<GgrsConfig>>,
+    mut inputs: ResMut<FrameInputs>,
+) {
+    inputs.0.clear();
+    inputs.0.extend(ggrs_inputs.iter().map(|(input, _)| *input));
+}
+
+pub fn input_diff_system(
+    inputs: Res<FrameInputs>,
+    mut query: Query<(&mut InputDiff, &Allegiance)>,
+) {
+    log::debug!("Registering input diffs");
+    for (mut input_diff, allegiance) in query.iter_mut() {
+        log::debug!("input diff: {:#?}", input_diff);
+        // Fighters left without an input, as when `step_frame` is
+        // given too few, are pressing nothing.
+        let new_input = inputs
+            .0
+            .get(allegiance.handle.0)
+            .copied()
+            .unwrap_or_else(CombinedInput::new);
+        for button in Button::iter() {
+            input_diff.set(button, new_input.get(button));
+        }
+        input_diff.stick.update(new_input.stick());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framedata::FULL_CHARGE;
+    use crate::headless;
+    use crate::input::STICK_MAX;
+    use crate::machine::postbox::{GroundedStance, PostboxState, Stance};
+
+    fn pushed(path: &[i8]) -> Stick {
+        let mut stick = Stick::default();
+        for x in path {
+            stick.update((*x, 0));
+        }
+        stick
+    }
+
+    #[test]
+    fn pushing_the_stick_hard_and_fast_is_a_flick() {
+        assert_eq!(pushed(&[0, STICK_MAX]).flick(), Some(Button::Right));
+        assert_eq!(pushed(&[0, 60, -STICK_MAX]).flick(), Some(Button::Left));
+        // Held too long to still count
+        assert_eq!(pushed(&[STICK_MAX; 6]).flick(), None);
+        // Pushed over slowly
+        assert_eq!(pushed(&[40, 50, 60, 70, 80, 100, STICK_MAX]).flick(), None);
+        // Not pushed far enough
+        assert_eq!(pushed(&[0, 80]).flick(), None);
+    }
+
+    #[test]
+    fn up_and_down_flicks_win_over_sideways_ones() {
+        let mut stick = Stick::default();
+        stick.update((STICK_MAX, -STICK_MAX));
+        assert_eq!(stick.flick(), Some(Button::Down));
+    }
+
+    // Two Postboxes standing on Battlefield, with the first one
+    // pressing `letters` after they have landed.
+    fn first_stance(letters: &[(u32, &str)]) -> PostboxState {
+        let mut app = headless::landed_app();
+        headless::play(&mut app, letters);
+        headless::postbox_state(&mut app, 0)
+    }
+
+    #[test]
+    fn hitting_as_the_stick_is_flicked_charges_a_smash() {
+        let state = first_stance(&[(1, "RFH")]);
+        assert_eq!(
+            state.stance,
+            Stance::Grounded(GroundedStance::FSmashCharging)
+        );
+        let state = first_stance(&[(1, "UFH")]);
+        assert_eq!(
+            state.stance,
+            Stance::Grounded(GroundedStance::USmashCharging)
+        );
+    }
+
+    #[test]
+    fn hitting_with_the_stick_held_tilts() {
+        let state = first_stance(&[(10, "RF"), (1, "RFH")]);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::FTilting));
+    }
+
+    #[test]
+    fn digital_directions_tilt_unless_pushed_all_the_way() {
+        let state = first_stance(&[(1, "RH")]);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::FTilting));
+        let state = first_stance(&[(1, "UH")]);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::UTilting));
+    }
+
+    #[test]
+    fn letting_go_of_hit_lets_the_smash_loose() {
+        let state = first_stance(&[(1, "RFH"), (20, "H"), (1, "-")]);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::FSmashing));
+        assert_eq!(state.charge, 20);
+
+        let state = first_stance(&[(1, "RFH"), (70, "H")]);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::FSmashing));
+        assert_eq!(state.charge, FULL_CHARGE);
+    }
+
+    #[test]
+    fn presses_count_for_their_window() {
+        let windows = HashMap::from([(Button::Hit, 3)]);
+        let mut buffer = InputBuffer::default();
+        let mut input = InputDiff::new();
+        input.set(Button::Hit, InputState::Activated);
+        input.set(Button::Jump, InputState::Activated);
+        buffer.update(input, &windows);
+        input.set(Button::Hit, InputState::NotActivated);
+        input.set(Button::Jump, InputState::NotActivated);
+        for frame in 1..4 {
+            buffer.update(input, &windows);
+            let buffered = buffer.apply(input);
+            assert_eq!(buffered.get(Button::Hit) == ButtonDiff::Pressed, frame < 3);
+            // Buttons without a window aren't buffered
+            assert_eq!(buffered.get(Button::Jump), input.get(Button::Jump));
+        }
+
+        let mut pressed = InputDiff::new();
+        pressed.press(Button::Hit);
+        buffer.update(pressed, &windows);
+        buffer.clear();
+        assert_eq!(buffer, InputBuffer::default());
+    }
+
+    #[test]
+    fn hitting_during_a_jab_jabs_again_once_it_can_be_cancelled() {
+        let state = first_stance(&[(1, "H"), (4, "-"), (1, "H"), (6, "-")]);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::Jabbing));
+        assert_eq!(state.countup, 1);
+    }
+
+    #[test]
+    fn hitting_too_early_in_a_jab_is_forgotten() {
+        let state = first_stance(&[(1, "H"), (1, "-"), (1, "H"), (8, "-")]);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::Jabbing));
+        assert_eq!(state.countup, 10);
+    }
+}
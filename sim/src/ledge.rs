@@ -0,0 +1,232 @@
+use crate::machine::types::Physics;
+use crate::world::{
+    Allegiance, Fighter, HangingFrom, Ledge, LedgeCooldown, Orientation, Platform, Position,
+    StandingOn, Velocity, FIGHTER_DIMENSIONS,
+};
+use bevy::log;
+use bevy::prelude::*;
+
+// How far a falling fighter can be from where it would hang
+// and still grab the ledge.
+pub const LEDGE_REACH: i32 = 24;
+// Frames after letting go of a ledge before a fighter can grab one again.
+pub const REGRAB_COOLDOWN: u8 = 30;
+
+// Fighters hang beside the platform, with the top of their body
+// level with the top of the platform.
+pub fn hang_position(platform: &Platform, side: Orientation) -> Position {
+    let x = match side {
+        Orientation::Left => platform.x - FIGHTER_DIMENSIONS,
+        Orientation::Right => platform.x + platform.width,
+    };
+    Position {
+        x,
+        y: platform.y - FIGHTER_DIMENSIONS,
+    }
+}
+
+// Climbing up from a ledge leaves the fighter standing at the edge
+// of the platform.
+pub fn climb_position(platform: &Platform, side: Orientation) -> Position {
+    let x = match side {
+        Orientation::Left => platform.x,
+        Orientation::Right => platform.x + platform.width - FIGHTER_DIMENSIONS,
+    };
+    Position {
+        x,
+        y: platform.y + 1,
+    }
+}
+
+fn facing_stage(side: Orientation) -> Orientation {
+    match side {
+        Orientation::Left => Orientation::Right,
+        Orientation::Right => Orientation::Left,
+    }
+}
+
+type LedgeFighterQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        (&'static Allegiance, &'static Physics),
+        (
+            &'static mut Position,
+            &'static mut Velocity,
+            &'static mut Orientation,
+        ),
+        &'static mut LedgeCooldown,
+        (Option<&'static HangingFrom>, Option<&'static StandingOn>),
+    ),
+    With<Fighter>,
+>;
+
+// Falling fighters near a free ledge grab it, and hanging fighters
+// whose stance has moved on let go of theirs. Only one fighter can
+// hang from a ledge at a time, and if two reach the same ledge on the
+// same frame the lower player handle gets it.
+pub fn ledge_system(
+    mut commands: Commands,
+    platform_query: Query<&Platform>,
+    mut fighter_query: LedgeFighterQuery,
+) {
+    log::debug!("ledge system beginning");
+    let platform_of = |ledge: &Ledge| platform_query.iter().find(|p| p.id == ledge.platform);
+    let ledges: Vec<(Ledge, &Platform)> = platform_query
+        .iter()
+        .flat_map(|platform| {
+            platform
+                .ledges()
+                .into_iter()
+                .map(move |ledge| (ledge, platform))
+        })
+        .collect();
+    let mut occupied: Vec<Ledge> = fighter_query
+        .iter()
+        .filter_map(|(_, _, _, _, (hanging_from, _))| hanging_from.map(|h| h.ledge))
+        .collect();
+
+    let mut fighters: Vec<_> = fighter_query.iter_mut().collect();
+    fighters.sort_by_key(|(_, (allegiance, _), _, _, _)| allegiance.handle.0);
+    for (
+        entity,
+        (_, physics),
+        (mut position, mut velocity, mut orientation),
+        mut cooldown,
+        (hanging_from, standing_on),
+    ) in fighters
+    {
+        if let Some(hanging_from) = hanging_from {
+            if *physics == Physics::Hanging {
+                continue;
+            }
+            log::trace!("Letting go of {:?}", hanging_from.ledge);
+            commands.entity(entity).remove::<HangingFrom>();
+            occupied.retain(|ledge| *ledge != hanging_from.ledge);
+            cooldown.frames = REGRAB_COOLDOWN;
+            if *physics == Physics::Climbing {
+                if let Some(platform) = platform_of(&hanging_from.ledge) {
+                    *position = climb_position(platform, hanging_from.ledge.side);
+                    commands.entity(entity).insert(StandingOn {
+                        platform: platform.id,
+                    });
+                }
+            }
+            continue;
+        }
+        if cooldown.frames > 0 {
+            cooldown.frames -= 1;
+            continue;
+        }
+        if standing_on.is_some() || *physics != Physics::Falling || velocity.y >= 0 {
+            continue;
+        }
+        let grabbed = ledges.iter().find(|(ledge, platform)| {
+            let hang = hang_position(platform, ledge.side);
+            !occupied.contains(ledge)
+                && (position.x - hang.x).abs() <= LEDGE_REACH
+                && (position.y - hang.y).abs() <= LEDGE_REACH
+        });
+        if let Some((ledge, platform)) = grabbed {
+            log::trace!("Grabbing {ledge:?}");
+            *position = hang_position(platform, ledge.side);
+            *velocity = Velocity { x: 0, y: 0 };
+            *orientation = facing_stage(ledge.side);
+            occupied.push(*ledge);
+            commands
+                .entity(entity)
+                .insert(HangingFrom { ledge: *ledge });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless;
+    use crate::machine::postbox::{AerialStance, PostboxState, Stance};
+    use crate::machine::types::Armour;
+
+    // Battlefield's main platform is solid, with its left ledge at (-300, 0).
+    // This is just off the left of it, falling past the ledge.
+    fn drop_by_ledge(app: &mut App, handle: usize) {
+        let entity = headless::fighter(app, handle);
+        app.world
+            .entity_mut(entity)
+            .insert(Position { x: -345, y: 10 })
+            .remove::<StandingOn>();
+    }
+
+    fn hanging_from(app: &mut App, handle: usize) -> Option<Ledge> {
+        let entity = headless::fighter(app, handle);
+        app.world
+            .get::<HangingFrom>(entity)
+            .map(|hanging_from| hanging_from.ledge)
+    }
+
+    fn position(app: &mut App, handle: usize) -> Position {
+        let entity = headless::fighter(app, handle);
+        app.world.get::<Position>(entity).unwrap().clone()
+    }
+
+    #[test]
+    fn falling_fighters_grab_ledges() {
+        let mut app = headless::test_app();
+        drop_by_ledge(&mut app, 0);
+        headless::step(&mut app, 20, &["-"]);
+
+        let ledge = hanging_from(&mut app, 0).expect("the fighter is hanging");
+        assert_eq!(ledge.side, Orientation::Left);
+        assert_eq!(position(&mut app, 0), Position { x: -340, y: -40 });
+        let entity = headless::fighter(&mut app, 0);
+        let state = app.world.get::<PostboxState>(entity).unwrap();
+        assert_eq!(state.stance, Stance::Aerial(AerialStance::LedgeHanging));
+        assert_eq!(
+            *app.world.get::<Orientation>(entity).unwrap(),
+            Orientation::Right
+        );
+        assert_eq!(
+            *app.world.get::<Armour>(entity).unwrap(),
+            Armour::Invincibility
+        );
+    }
+
+    #[test]
+    fn climbing_up_stands_on_the_stage() {
+        let mut app = headless::test_app();
+        drop_by_ledge(&mut app, 0);
+        headless::step(&mut app, 20, &["-"]);
+        headless::step(&mut app, 1, &["U"]);
+        headless::step(&mut app, 30, &["-"]);
+
+        assert_eq!(hanging_from(&mut app, 0), None);
+        assert_eq!(position(&mut app, 0), Position { x: -300, y: 1 });
+        let entity = headless::fighter(&mut app, 0);
+        assert!(app.world.get::<StandingOn>(entity).is_some());
+    }
+
+    #[test]
+    fn dropping_off_does_not_grab_straight_back_on() {
+        let mut app = headless::test_app();
+        drop_by_ledge(&mut app, 0);
+        headless::step(&mut app, 20, &["-"]);
+        headless::step(&mut app, 1, &["D"]);
+        headless::step(&mut app, 20, &["-"]);
+
+        assert_eq!(hanging_from(&mut app, 0), None);
+        assert!(position(&mut app, 0).y < -40 - LEDGE_REACH);
+    }
+
+    #[test]
+    fn only_one_fighter_hangs_from_a_ledge() {
+        let mut app = headless::test_app();
+        drop_by_ledge(&mut app, 0);
+        drop_by_ledge(&mut app, 1);
+        headless::step(&mut app, 20, &["-"]);
+
+        assert!(hanging_from(&mut app, 0).is_some());
+        assert_eq!(hanging_from(&mut app, 1), None);
+        assert!(position(&mut app, 1).y < -40 - LEDGE_REACH);
+    }
+}
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use bevy_ggrs::{GgrsAppExtension, GgrsPlugin, GgrsSchedule, PlayerInputs};
+use ggrs::PlayerHandle;
+
+use input::CombinedInput;
+use machine::celeste::Celeste;
+use machine::postbox::Postbox;
+
+pub mod checksum;
+pub mod combat;
+pub mod death;
+pub mod framedata;
+pub mod grab;
+pub mod headless;
+pub mod input;
+pub mod intent;
+pub mod ledge;
+pub mod machine;
+pub mod physics;
+pub mod projectile;
+pub mod replay;
+pub mod rollback;
+pub mod shield;
+pub mod snapshot;
+pub mod stage;
+pub mod synctest;
+pub mod types;
+pub mod world;
+
+pub const FPS: usize = 60;
+
+// Everything which is rolled back, and the systems which advance it a
+// frame at a time. GGRS only runs these once both players are
+// synchronized, which is the same frame for both of them.
+pub fn add_simulation<Params>(
+    app: &mut App,
+    input_system: impl IntoSystem<PlayerHandle, CombinedInput, Params>,
+) -> &mut App {
+    let plugin = GgrsPlugin::<types::GgrsConfig>::new()
+        .with_update_frequency(FPS)
+        .with_input_system(input_system)
+        .register_rollback_resource::<world::FrameCount>()
+        .register_rollback_resource::<checksum::Checksum>()
+        .register_rollback_component::<projectile::Projectile>();
+    app.add_asset::<framedata::FrameDataTable>()
+        .init_asset_loader::<framedata::FrameDataLoader>()
+        .add_asset::<stage::Stage>()
+        .init_asset_loader::<stage::StageLoader>()
+        .add_ggrs_plugin(rollback::register_fighter_components(plugin))
+        .init_resource::<world::FrameCount>()
+        .init_resource::<checksum::Checksum>()
+        .init_resource::<checksum::ChecksumHistory>()
+        .init_resource::<intent::FrameInputs>()
+        .init_resource::<death::InfiniteStocks>()
+        .add_systems(
+            GgrsSchedule,
+            (
+                world::frame_count_system,
+                intent::frame_inputs_system
+                    .run_if(resource_exists::<PlayerInputs<types::GgrsConfig>>()),
+                intent::input_diff_system,
+                machine::input_system::<Postbox>,
+                machine::input_system::<Celeste>,
+                (
+                    projectile::spawn_projectile_system::<Postbox>,
+                    projectile::spawn_projectile_system::<Celeste>,
+                )
+                    .chain(),
+                machine::physics_system::<Postbox>,
+                machine::physics_system::<Celeste>,
+                physics::acceleration_system,
+                physics::movement_system,
+                ledge::ledge_system,
+                grab::grab_system,
+                shield::shield_system,
+                combat::hit_system::<Postbox>,
+                combat::hit_system::<Celeste>,
+                projectile::projectile_system,
+                death::death_system,
+                checksum::checksum_system,
+            )
+                .chain(),
+        )
+}
+
+// Simulates one frame outside of GGRS with the given inputs, by player
+// handle. Nothing is saved for rolling back.
+pub fn step_frame(world: &mut World, inputs: Vec<CombinedInput>) {
+    world.resource_mut::<intent::FrameInputs>().0 = inputs;
+    world.run_schedule(GgrsSchedule);
+}
@@ -0,0 +1,460 @@
+pub mod celeste;
+pub mod postbox;
+pub mod types;
+
+use crate::framedata::{Attributes, FrameDataAssets, FrameDataTable};
+use crate::grab::{HeldBy, Holding};
+use crate::input::{Button, STICK_TILT};
+use crate::intent::STICK_NEUTRAL;
+use crate::machine::types::{Armour, Physics};
+use crate::physics::TERMINAL_VELOCITY;
+use crate::shield::Shield;
+use crate::world::{
+    Acceleration, ButtonDiff, HangingFrom, Hitstun, InputBuffer, InputDiff, Orientation, Platform,
+    PlatformKind, Position, StandingOn, Velocity,
+};
+use bevy::log;
+use bevy::prelude::*;
+use bevy::reflect::TypePath;
+
+use std::fmt::Debug;
+use std::hash::Hash;
+
+// The stances of one character. Every stance has an entry
+// in that character's frame data file under its name.
+pub trait StanceKind:
+    Copy + Clone + Debug + Default + PartialEq + Eq + Hash + Reflect + TypePath + Send + Sync + 'static
+{
+    const ALL: &'static [Self];
+
+    fn name(self) -> &'static str;
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|stance| stance.name() == name)
+    }
+
+    fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|stance| *stance == self)
+            .expect("every stance is listed in StanceKind::ALL")
+    }
+}
+
+// Describes how a character moves from stance to stance. The systems
+// in this module run the state machine for every fighter of the character.
+pub trait Character: Send + Sync + 'static {
+    type Stance: StanceKind;
+
+    // The name of the character in frame data files.
+    const NAME: &'static str;
+    // The stance a fighter is put in while in hitstun.
+    const HITSTUN: Self::Stance;
+    // The stances a fighter is put in when a hit on its shield stuns it,
+    // and when its shield breaks. Only characters with a shield have them.
+    const SHIELD_STUN: Option<Self::Stance> = None;
+    const SHIELD_BREAK: Option<Self::Stance> = None;
+
+    fn frame_data(assets: &FrameDataAssets) -> &Handle<FrameDataTable>;
+
+    fn timeout_stance(stance: Self::Stance) -> Self::Stance;
+
+    // Landing on a platform, leaving one or grabbing a ledge
+    // overrides whatever the fighter was doing.
+    fn environment_stance(stance: Self::Stance, environment: Environment) -> Option<Self::Stance>;
+
+    // Being grabbed, getting a hold of whoever was grabbed and
+    // being let go all override whatever the fighter was doing.
+    fn grab_stance(stance: Self::Stance, tether: Tether) -> Option<Self::Stance>;
+
+    // The stance the player's input moves the fighter into, if any.
+    fn input_map(
+        stance: Self::Stance,
+        state: &FighterState<Self::Stance>,
+        orientation: Orientation,
+        attributes: &Attributes,
+        input: InputDiff,
+    ) -> Option<Self::Stance>;
+
+    // Whether the fighter turns to face the direction being held.
+    fn can_turn(state: &FighterState<Self::Stance>) -> bool;
+
+    // Whether pressing down drops the fighter through the soft
+    // platform it is standing on.
+    fn can_drop_through(state: &FighterState<Self::Stance>) -> bool;
+
+    // Called just before the fighter enters a new stance.
+    fn enter_stance(_state: &mut FighterState<Self::Stance>, _new_stance: Self::Stance) {}
+}
+
+// Where a fighter is, as far as the stage is concerned.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Environment {
+    Airborne,
+    Grounded,
+    OnLedge,
+}
+
+// Which end of a grab a fighter is on, if either.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tether {
+    Free,
+    Holding,
+    Held,
+}
+
+#[derive(Component, Reflect, Debug, Clone, Hash)]
+pub struct FighterState<S: StanceKind> {
+    pub stance: S,
+    pub countdown: i8,
+    pub countup: u8,
+    // Aerial jumps taken since the fighter last stood on a platform.
+    pub jumps: u8,
+    // Frames the attack the fighter is making was charged for.
+    pub charge: u8,
+}
+
+impl<S: StanceKind> Default for FighterState<S> {
+    fn default() -> Self {
+        FighterState {
+            stance: S::default(),
+            countdown: -1,
+            countup: 0,
+            jumps: 0,
+            charge: 0,
+        }
+    }
+}
+
+fn update_stance<C: Character>(
+    state: &mut FighterState<C::Stance>,
+    table: &FrameDataTable,
+    new_stance: C::Stance,
+) {
+    C::enter_stance(state, new_stance);
+    state.countup = 0;
+    log::trace!("Executing frame 1 of {:?}", new_stance);
+    state.countdown = table.get(new_stance).timeout;
+    state.stance = new_stance;
+    assert!(state.countdown != 0);
+}
+
+fn tick_stance<C: Character>(state: &mut FighterState<C::Stance>, table: &FrameDataTable) {
+    state.countup = state.countup.wrapping_add(1);
+    if state.countdown > 0 {
+        state.countdown -= 1;
+    }
+    assert!(state.countdown >= -1);
+    if state.countdown == 0 {
+        log::trace!("Stance timeout");
+        let new_stance = C::timeout_stance(state.stance);
+        update_stance::<C>(state, table, new_stance);
+    } else {
+        log::trace!(
+            "Executing frame {:?} of {:?}",
+            state.countup + 1,
+            state.stance
+        );
+    }
+}
+
+// During a cancel window the stance is as actionable as the stance
+// it would time out into, but only for the stances listed in the window.
+fn cancel_input_map<C: Character>(
+    table: &FrameDataTable,
+    state: &FighterState<C::Stance>,
+    orientation: Orientation,
+    input: InputDiff,
+) -> Option<C::Stance> {
+    let frame = state.countup;
+    let timeout_stance = C::timeout_stance(state.stance);
+    let new_stance = C::input_map(timeout_stance, state, orientation, &table.attributes, input)?;
+    if table.get(state.stance).cancellable_into(frame, new_stance) {
+        log::trace!("Cancelling {:?} into {new_stance:?}", state.stance);
+        Some(new_stance)
+    } else {
+        None
+    }
+}
+
+pub fn pressed(input: InputDiff, button: Button) -> bool {
+    input.get(button) == ButtonDiff::Pressed
+}
+
+pub fn held(input: InputDiff, button: Button) -> bool {
+    input.is_being_pressed(button)
+}
+
+// The direction being held, if exactly one of left and right is.
+pub fn held_direction(input: InputDiff) -> Option<Orientation> {
+    match (held(input, Button::Left), held(input, Button::Right)) {
+        (true, false) => Some(Orientation::Left),
+        (false, true) => Some(Orientation::Right),
+        _ => None,
+    }
+}
+
+// What a fighter hanging from a ledge does, the same for every character.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LedgeAction {
+    Climb,
+    Jump,
+    Drop,
+}
+
+// Fighters on a ledge face the stage, so climbing is towards it.
+pub fn ledge_action(orientation: Orientation, input: InputDiff) -> Option<LedgeAction> {
+    let towards = match orientation {
+        Orientation::Right => Button::Right,
+        Orientation::Left => Button::Left,
+    };
+    let away = match orientation {
+        Orientation::Right => Button::Left,
+        Orientation::Left => Button::Right,
+    };
+    if pressed(input, Button::Jump) {
+        Some(LedgeAction::Jump)
+    } else if pressed(input, Button::Up) || pressed(input, towards) {
+        Some(LedgeAction::Climb)
+    } else if pressed(input, Button::Down) || pressed(input, away) {
+        Some(LedgeAction::Drop)
+    } else {
+        None
+    }
+}
+
+pub fn input_system<C: Character>(
+    mut commands: Commands,
+    frame_data: Res<FrameDataAssets>,
+    tables: Res<Assets<FrameDataTable>>,
+    platforms: Query<&Platform>,
+    mut query: Query<(
+        Entity,
+        (
+            &mut FighterState<C::Stance>,
+            &mut Orientation,
+            &mut Physics,
+            &mut Armour,
+            &mut Hitstun,
+            &mut Shield,
+        ),
+        &mut Position,
+        (&InputDiff, &mut InputBuffer),
+        (Option<&StandingOn>, Option<&HangingFrom>),
+        (Option<&Holding>, Option<&HeldBy>),
+    )>,
+) {
+    log::debug!("{} input system beginning", C::NAME);
+    let table = tables
+        .get(C::frame_data(&frame_data))
+        .expect("frame data is loaded before the game starts");
+    for (
+        entity,
+        (mut state, mut orientation, mut physics, mut armour, mut hitstun, mut shield),
+        mut position,
+        (input, mut buffer),
+        (standing_on, hanging_from),
+        (holding, held_by),
+    ) in query.iter_mut()
+    {
+        buffer.update(*input, &table.attributes.buffer);
+        // What the fighter does is decided as if presses
+        // which still count were made again.
+        let buffered = buffer.apply(*input);
+        let tether = match (holding, held_by) {
+            (_, Some(_)) => Tether::Held,
+            (Some(_), None) => Tether::Holding,
+            (None, None) => Tether::Free,
+        };
+        let mut environment = match (standing_on, hanging_from) {
+            (Some(_), _) => Environment::Grounded,
+            (None, Some(_)) => Environment::OnLedge,
+            (None, None) => Environment::Airborne,
+        };
+        let soft_platform = standing_on
+            .and_then(|standing_on| platforms.iter().find(|p| p.id == standing_on.platform))
+            .filter(|platform| platform.kind == PlatformKind::Soft);
+        if let Some(platform) = soft_platform {
+            if hitstun.frames == 0 && pressed(*input, Button::Down) && C::can_drop_through(&state) {
+                // Just below the top, so the fighter isn't landed on it again
+                log::trace!("Dropping through a platform");
+                position.y = platform.y - 1;
+                commands.entity(entity).remove::<StandingOn>();
+                environment = Environment::Airborne;
+            }
+        }
+        if hitstun.frames > 0 {
+            log::trace!("Entering hitstun for {} frames", hitstun.frames);
+            update_stance::<C>(&mut state, table, C::HITSTUN);
+            // Hitstun lasts as long as the hit says, not the frame data
+            state.countdown = hitstun.frames;
+            hitstun.frames = 0;
+        } else if let Some(stance) = C::SHIELD_BREAK.filter(|_| shield.broken) {
+            update_stance::<C>(&mut state, table, stance);
+            shield.broken = false;
+        } else if let Some(stance) = C::SHIELD_STUN.filter(|_| shield.stun > 0) {
+            log::trace!("Entering shield stun for {} frames", shield.stun);
+            update_stance::<C>(&mut state, table, stance);
+            state.countdown = shield.stun;
+            shield.stun = 0;
+        } else if let Some(new_stance) = C::grab_stance(state.stance, tether)
+            // Whoever is holding the fighter decides where it is
+            .or_else(|| match tether {
+                Tether::Held => None,
+                _ => C::environment_stance(state.stance, environment),
+            })
+        {
+            log::trace!("Setting stance to {new_stance:?}");
+            update_stance::<C>(&mut state, table, new_stance);
+        } else if let Some(new_stance) = C::input_map(
+            state.stance,
+            &state,
+            *orientation,
+            &table.attributes,
+            buffered,
+        )
+        .or_else(|| cancel_input_map::<C>(table, &state, *orientation, buffered))
+        {
+            log::trace!("Setting stance to {new_stance:?} from input");
+            update_stance::<C>(&mut state, table, new_stance);
+            buffer.clear();
+        } else {
+            tick_stance::<C>(&mut state, table);
+        }
+        if C::can_turn(&state) {
+            if let Some(direction) = held_direction(*input) {
+                *orientation = direction;
+            }
+        }
+        let frame_data = table.get(state.stance).frame(state.countup);
+        *armour = frame_data.armour;
+        *physics = frame_data.physics;
+    }
+}
+
+fn air_drift(
+    vel: &mut Velocity,
+    acc: &mut Acceleration,
+    attributes: &Attributes,
+    input: InputDiff,
+) {
+    match held_direction(input) {
+        Some(Orientation::Right) => {
+            vel.x = attributes.air_speed;
+            acc.x = 0;
+        }
+        Some(Orientation::Left) => {
+            vel.x = -attributes.air_speed;
+            acc.x = 0;
+        }
+        None => acc.x = -vel.x.signum(),
+    }
+}
+
+fn gravity(vel: &Velocity, acc: &mut Acceleration, attributes: &Attributes) {
+    acc.y = if vel.y > -TERMINAL_VELOCITY {
+        -attributes.gravity
+    } else {
+        0
+    };
+}
+
+// Slows a fighter sliding along the ground without letting
+// it slide back the other way.
+fn traction(vel: &mut Velocity, acc: &mut Acceleration, attributes: &Attributes) {
+    if vel.x.abs() <= attributes.traction {
+        vel.x = 0;
+        acc.x = 0;
+    } else {
+        acc.x = -vel.x.signum() * attributes.traction;
+    }
+}
+
+// Fighters walk slower the less the stick is pushed, up to full
+// speed as far as a key pushes it, but always move at least a
+// pixel a frame.
+fn walk_speed(attributes: &Attributes, input: InputDiff) -> i32 {
+    let tilt = input.stick.tilt_x().clamp(STICK_NEUTRAL, STICK_TILT as u8);
+    (attributes.walk_speed * tilt as i32 / STICK_TILT as i32).max(1)
+}
+
+fn ground_speed(orientation: Orientation, speed: i32) -> i32 {
+    match orientation {
+        Orientation::Right => speed,
+        Orientation::Left => -speed,
+    }
+}
+
+pub fn physics_system<C: Character>(
+    frame_data: Res<FrameDataAssets>,
+    tables: Res<Assets<FrameDataTable>>,
+    mut query: Query<
+        (
+            &Physics,
+            &Orientation,
+            &InputDiff,
+            &mut Velocity,
+            &mut Acceleration,
+        ),
+        With<FighterState<C::Stance>>,
+    >,
+) {
+    log::debug!("{} physics system beginning", C::NAME);
+    let table = tables
+        .get(C::frame_data(&frame_data))
+        .expect("frame data is loaded before the game starts");
+    let attributes = &table.attributes;
+    for (physics, orientation, input, mut vel, mut acc) in query.iter_mut() {
+        match physics {
+            Physics::NotMoving
+            | Physics::Hanging
+            | Physics::Climbing
+            | Physics::Holding
+            | Physics::Held => {
+                vel.x = 0;
+                vel.y = 0;
+                acc.x = 0;
+                acc.y = 0;
+            }
+            Physics::Walking
+            | Physics::Dashing
+            | Physics::Running
+            | Physics::Crawling
+            | Physics::Rolling => {
+                let speed = match physics {
+                    Physics::Walking => walk_speed(attributes, *input),
+                    Physics::Dashing => attributes.dash_speed,
+                    Physics::Running => attributes.run_speed,
+                    Physics::Crawling => attributes.crawl_speed,
+                    _ => attributes.roll_speed,
+                };
+                vel.x = ground_speed(*orientation, speed);
+                vel.y = 0;
+                acc.x = 0;
+                acc.y = 0;
+            }
+            Physics::Skidding => {
+                vel.y = 0;
+                acc.y = 0;
+                traction(&mut vel, &mut acc, attributes);
+            }
+            Physics::Jumping => {
+                vel.y = attributes.jump_velocity;
+                acc.y = 0;
+                air_drift(&mut vel, &mut acc, attributes, *input);
+            }
+            Physics::Falling => {
+                air_drift(&mut vel, &mut acc, attributes, *input);
+                gravity(&vel, &mut acc, attributes);
+            }
+            // Launch momentum is slowed by drag and pulled down by gravity
+            Physics::Tumbling => {
+                acc.x = -vel.x.signum();
+                gravity(&vel, &mut acc, attributes);
+            }
+        }
+    }
+}
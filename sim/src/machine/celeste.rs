@@ -0,0 +1,272 @@
+use crate::framedata::{Attributes, FrameDataAssets, FrameDataTable};
+use crate::input::Button;
+use crate::machine::{
+    held_direction, ledge_action, pressed, Character, Environment, FighterState, LedgeAction,
+    StanceKind, Tether,
+};
+use crate::world::{InputDiff, Orientation};
+use bevy::prelude::*;
+
+#[derive(Copy, Clone, Debug, Default, Reflect, PartialEq, Eq, Hash)]
+pub enum GroundedStance {
+    #[default]
+    Standing,
+    Walking,
+    JumpSquat,
+    Jabbing,
+    LedgeClimbing,
+    Held,
+}
+
+#[derive(Copy, Clone, Debug, Default, Reflect, PartialEq, Eq, Hash)]
+pub enum AerialStance {
+    #[default]
+    Falling,
+    Jumping,
+    NAiring,
+    Tumbling,
+    LedgeHanging,
+}
+
+#[derive(Copy, Clone, Debug, Reflect, PartialEq, Eq, Hash)]
+pub enum Stance {
+    Grounded(GroundedStance),
+    Aerial(AerialStance),
+}
+
+impl StanceKind for Stance {
+    const ALL: &'static [Stance] = &[
+        Stance::Grounded(GroundedStance::Standing),
+        Stance::Grounded(GroundedStance::Walking),
+        Stance::Grounded(GroundedStance::JumpSquat),
+        Stance::Grounded(GroundedStance::Jabbing),
+        Stance::Grounded(GroundedStance::LedgeClimbing),
+        Stance::Grounded(GroundedStance::Held),
+        Stance::Aerial(AerialStance::Falling),
+        Stance::Aerial(AerialStance::Jumping),
+        Stance::Aerial(AerialStance::NAiring),
+        Stance::Aerial(AerialStance::Tumbling),
+        Stance::Aerial(AerialStance::LedgeHanging),
+    ];
+
+    fn name(self) -> &'static str {
+        use self::AerialStance as A;
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        match self {
+            S::Grounded(G::Standing) => "standing",
+            S::Grounded(G::Walking) => "walking",
+            S::Grounded(G::JumpSquat) => "jump_squat",
+            S::Grounded(G::Jabbing) => "jabbing",
+            S::Grounded(G::LedgeClimbing) => "ledge_climbing",
+            S::Grounded(G::Held) => "held",
+            S::Aerial(A::Falling) => "falling",
+            S::Aerial(A::Jumping) => "jumping",
+            S::Aerial(A::NAiring) => "nairing",
+            S::Aerial(A::Tumbling) => "tumbling",
+            S::Aerial(A::LedgeHanging) => "ledge_hanging",
+        }
+    }
+}
+
+impl Default for Stance {
+    fn default() -> Self {
+        Stance::Grounded(GroundedStance::default())
+    }
+}
+
+pub type CelesteState = FighterState<Stance>;
+
+fn standing_input_map(input: InputDiff) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    if pressed(input, Button::Jump) {
+        Some(G::JumpSquat)
+    } else if pressed(input, Button::Hit) {
+        Some(G::Jabbing)
+    } else if held_direction(input).is_some() {
+        Some(G::Walking)
+    } else {
+        None
+    }
+}
+
+fn walking_input_map(input: InputDiff) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    if pressed(input, Button::Jump) {
+        Some(G::JumpSquat)
+    } else if pressed(input, Button::Hit) {
+        Some(G::Jabbing)
+    } else if held_direction(input).is_none() {
+        Some(G::Standing)
+    } else {
+        None
+    }
+}
+
+fn airborne_input_map(jumps: u8, max_jumps: u8, input: InputDiff) -> Option<AerialStance> {
+    use self::AerialStance as A;
+    if pressed(input, Button::Jump) && jumps < max_jumps {
+        Some(A::Jumping)
+    } else if pressed(input, Button::Hit) {
+        Some(A::NAiring)
+    } else {
+        None
+    }
+}
+
+pub struct Celeste;
+
+impl Character for Celeste {
+    type Stance = Stance;
+
+    const NAME: &'static str = "celeste";
+    const HITSTUN: Stance = Stance::Aerial(AerialStance::Tumbling);
+
+    fn frame_data(assets: &FrameDataAssets) -> &Handle<FrameDataTable> {
+        &assets.celeste
+    }
+
+    fn timeout_stance(stance: Stance) -> Stance {
+        use self::AerialStance as A;
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        match stance {
+            S::Grounded(G::JumpSquat) => S::Aerial(A::Jumping),
+            S::Aerial(_) => S::Aerial(A::Falling),
+            S::Grounded(_) => S::Grounded(G::Standing),
+        }
+    }
+
+    fn environment_stance(stance: Stance, environment: Environment) -> Option<Stance> {
+        use self::AerialStance as A;
+        use self::Environment as E;
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        match (stance, environment) {
+            (S::Aerial(A::LedgeHanging), E::OnLedge) => None,
+            (_, E::OnLedge) => Some(S::Aerial(A::LedgeHanging)),
+            // Hitstun plays out in full even once the fighter is back down
+            (S::Aerial(A::Tumbling), E::Grounded) => None,
+            (S::Aerial(_), E::Grounded) => Some(S::Grounded(G::Standing)),
+            (S::Grounded(_) | S::Aerial(A::LedgeHanging), E::Airborne) => {
+                Some(S::Aerial(A::Falling))
+            }
+            _ => None,
+        }
+    }
+
+    // Celeste can't grab, but can be grabbed.
+    fn grab_stance(stance: Stance, tether: Tether) -> Option<Stance> {
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        match (stance, tether) {
+            (S::Grounded(G::Held), Tether::Held) => None,
+            (_, Tether::Held) => Some(S::Grounded(G::Held)),
+            (S::Grounded(G::Held), _) => Some(S::Grounded(G::Standing)),
+            _ => None,
+        }
+    }
+
+    fn input_map(
+        stance: Stance,
+        state: &CelesteState,
+        orientation: Orientation,
+        attributes: &Attributes,
+        input: InputDiff,
+    ) -> Option<Stance> {
+        use self::AerialStance as A;
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        match stance {
+            S::Aerial(A::LedgeHanging) => {
+                ledge_action(orientation, input).map(|action| match action {
+                    LedgeAction::Climb => S::Grounded(G::LedgeClimbing),
+                    LedgeAction::Jump => S::Aerial(A::Jumping),
+                    LedgeAction::Drop => S::Aerial(A::Falling),
+                })
+            }
+            S::Grounded(G::Standing) => standing_input_map(input).map(S::Grounded),
+            S::Grounded(G::Walking) => walking_input_map(input).map(S::Grounded),
+            S::Aerial(A::Falling | A::Jumping) => {
+                airborne_input_map(state.jumps, attributes.max_aerial_jumps, input).map(S::Aerial)
+            }
+            S::Grounded(G::JumpSquat | G::Jabbing | G::LedgeClimbing | G::Held)
+            | S::Aerial(A::NAiring | A::Tumbling) => None,
+        }
+    }
+
+    fn can_turn(state: &CelesteState) -> bool {
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        matches!(state.stance, S::Grounded(G::Standing | G::Walking))
+    }
+
+    fn can_drop_through(state: &CelesteState) -> bool {
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        matches!(state.stance, S::Grounded(G::Standing | G::Walking))
+    }
+
+    fn enter_stance(state: &mut CelesteState, new_stance: Stance) {
+        use self::AerialStance as A;
+        use self::Stance as S;
+        // Grabbing a ledge gives the fighter its jumps back,
+        // and jumping from one doesn't use any up.
+        match (state.stance, new_stance) {
+            (S::Aerial(A::LedgeHanging), S::Aerial(A::Jumping)) => (),
+            (S::Aerial(_), S::Aerial(A::Jumping)) => state.jumps += 1,
+            (_, S::Grounded(_) | S::Aerial(A::LedgeHanging)) => state.jumps = 0,
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless;
+    use crate::input::InputSource;
+    use crate::stage::StageId;
+    use crate::world::CharacterId;
+
+    // Two Celestes standing on Battlefield, with the first one
+    // pressing `letters` after they have landed.
+    fn first_state(letters: &[(u32, &str)]) -> CelesteState {
+        let mut app = headless::headless_app(
+            &[CharacterId::Celeste, CharacterId::Celeste],
+            StageId::Battlefield,
+            InputSource::Device,
+            None,
+        );
+        app.update();
+        headless::step(&mut app, 30, &[]);
+        headless::play(&mut app, letters);
+        let entity = headless::fighter(&mut app, 0);
+        app.world.get::<CelesteState>(entity).unwrap().clone()
+    }
+
+    #[test]
+    fn jabs_last_as_long_as_their_frame_data() {
+        let state = first_state(&[(1, "H"), (17, "-")]);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::Jabbing));
+        assert_eq!(state.countup, 17);
+
+        let state = first_state(&[(1, "H"), (18, "-")]);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::Standing));
+    }
+
+    #[test]
+    fn jumps_squat_before_leaving_the_ground() {
+        let state = first_state(&[(1, "J"), (2, "-")]);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::JumpSquat));
+
+        let state = first_state(&[(1, "J"), (3, "-")]);
+        assert_eq!(state.stance, Stance::Aerial(AerialStance::Jumping));
+    }
+
+    #[test]
+    fn hitting_in_the_air_nairs() {
+        let state = first_state(&[(1, "J"), (5, "-"), (1, "H")]);
+        assert_eq!(state.stance, Stance::Aerial(AerialStance::NAiring));
+    }
+}
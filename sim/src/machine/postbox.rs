@@ -0,0 +1,654 @@
+use crate::framedata::{Attributes, FrameDataAssets, FrameDataTable};
+use crate::input::Button;
+use crate::machine::{
+    held, held_direction, ledge_action, pressed, Character, Environment, FighterState, LedgeAction,
+    StanceKind, Tether,
+};
+use crate::world::{InputDiff, Orientation};
+use bevy::prelude::*;
+
+#[derive(Copy, Clone, Debug, Default, Reflect, PartialEq, Eq, Hash)]
+pub enum GroundedStance {
+    #[default]
+    Standing,
+    Walking,
+    // A burst of speed from a flick of the stick, which can be turned
+    // back on until it becomes a run.
+    Dashing,
+    Running,
+    Skidding,
+    RunTurning,
+    Crouching,
+    Crawling,
+    JumpSquat,
+    Jabbing,
+    FTilting,
+    UTilting,
+    DTilting,
+    // Smash attacks charge for as long as hit is held.
+    FSmashCharging,
+    USmashCharging,
+    DSmashCharging,
+    FSmashing,
+    USmashing,
+    DSmashing,
+    LedgeClimbing,
+    Shielding,
+    ShieldStunned,
+    ShieldBroken,
+    SpotDodging,
+    Rolling,
+    Grabbing,
+    Holding,
+    Pummeling,
+    FThrowing,
+    BThrowing,
+    UThrowing,
+    DThrowing,
+    Held,
+    LetterThrowing,
+}
+
+#[derive(Copy, Clone, Debug, Default, Reflect, PartialEq, Eq, Hash)]
+pub enum AerialStance {
+    #[default]
+    Falling,
+    Jumping,
+    NAiring,
+    FAiring,
+    BAiring,
+    UAiring,
+    DAiring,
+    Tumbling,
+    LedgeHanging,
+    AirDodging,
+    // After an air dodge, until the fighter lands or grabs a ledge.
+    Helpless,
+    AirLetterThrowing,
+}
+
+#[derive(Copy, Clone, Debug, Reflect, PartialEq, Eq, Hash)]
+pub enum Stance {
+    Grounded(GroundedStance),
+    Aerial(AerialStance),
+}
+
+impl StanceKind for Stance {
+    const ALL: &'static [Stance] = &[
+        Stance::Grounded(GroundedStance::Standing),
+        Stance::Grounded(GroundedStance::Walking),
+        Stance::Grounded(GroundedStance::Dashing),
+        Stance::Grounded(GroundedStance::Running),
+        Stance::Grounded(GroundedStance::Skidding),
+        Stance::Grounded(GroundedStance::RunTurning),
+        Stance::Grounded(GroundedStance::Crouching),
+        Stance::Grounded(GroundedStance::Crawling),
+        Stance::Grounded(GroundedStance::JumpSquat),
+        Stance::Grounded(GroundedStance::Jabbing),
+        Stance::Grounded(GroundedStance::FTilting),
+        Stance::Grounded(GroundedStance::UTilting),
+        Stance::Grounded(GroundedStance::DTilting),
+        Stance::Grounded(GroundedStance::FSmashCharging),
+        Stance::Grounded(GroundedStance::USmashCharging),
+        Stance::Grounded(GroundedStance::DSmashCharging),
+        Stance::Grounded(GroundedStance::FSmashing),
+        Stance::Grounded(GroundedStance::USmashing),
+        Stance::Grounded(GroundedStance::DSmashing),
+        Stance::Grounded(GroundedStance::LedgeClimbing),
+        Stance::Grounded(GroundedStance::Shielding),
+        Stance::Grounded(GroundedStance::ShieldStunned),
+        Stance::Grounded(GroundedStance::ShieldBroken),
+        Stance::Grounded(GroundedStance::SpotDodging),
+        Stance::Grounded(GroundedStance::Rolling),
+        Stance::Grounded(GroundedStance::Grabbing),
+        Stance::Grounded(GroundedStance::Holding),
+        Stance::Grounded(GroundedStance::Pummeling),
+        Stance::Grounded(GroundedStance::FThrowing),
+        Stance::Grounded(GroundedStance::BThrowing),
+        Stance::Grounded(GroundedStance::UThrowing),
+        Stance::Grounded(GroundedStance::DThrowing),
+        Stance::Grounded(GroundedStance::Held),
+        Stance::Grounded(GroundedStance::LetterThrowing),
+        Stance::Aerial(AerialStance::Falling),
+        Stance::Aerial(AerialStance::Jumping),
+        Stance::Aerial(AerialStance::NAiring),
+        Stance::Aerial(AerialStance::FAiring),
+        Stance::Aerial(AerialStance::BAiring),
+        Stance::Aerial(AerialStance::UAiring),
+        Stance::Aerial(AerialStance::DAiring),
+        Stance::Aerial(AerialStance::Tumbling),
+        Stance::Aerial(AerialStance::LedgeHanging),
+        Stance::Aerial(AerialStance::AirDodging),
+        Stance::Aerial(AerialStance::Helpless),
+        Stance::Aerial(AerialStance::AirLetterThrowing),
+    ];
+
+    fn name(self) -> &'static str {
+        use self::AerialStance as A;
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        match self {
+            S::Grounded(G::Standing) => "standing",
+            S::Grounded(G::Walking) => "walking",
+            S::Grounded(G::Dashing) => "dashing",
+            S::Grounded(G::Running) => "running",
+            S::Grounded(G::Skidding) => "skidding",
+            S::Grounded(G::RunTurning) => "run_turning",
+            S::Grounded(G::Crouching) => "crouching",
+            S::Grounded(G::Crawling) => "crawling",
+            S::Grounded(G::JumpSquat) => "jump_squat",
+            S::Grounded(G::Jabbing) => "jabbing",
+            S::Grounded(G::FTilting) => "ftilting",
+            S::Grounded(G::UTilting) => "utilting",
+            S::Grounded(G::DTilting) => "dtilting",
+            S::Grounded(G::FSmashCharging) => "fsmash_charging",
+            S::Grounded(G::USmashCharging) => "usmash_charging",
+            S::Grounded(G::DSmashCharging) => "dsmash_charging",
+            S::Grounded(G::FSmashing) => "fsmashing",
+            S::Grounded(G::USmashing) => "usmashing",
+            S::Grounded(G::DSmashing) => "dsmashing",
+            S::Grounded(G::LedgeClimbing) => "ledge_climbing",
+            S::Grounded(G::Shielding) => "shielding",
+            S::Grounded(G::ShieldStunned) => "shield_stunned",
+            S::Grounded(G::ShieldBroken) => "shield_broken",
+            S::Grounded(G::SpotDodging) => "spot_dodging",
+            S::Grounded(G::Rolling) => "rolling",
+            S::Grounded(G::Grabbing) => "grabbing",
+            S::Grounded(G::Holding) => "holding",
+            S::Grounded(G::Pummeling) => "pummeling",
+            S::Grounded(G::FThrowing) => "fthrowing",
+            S::Grounded(G::BThrowing) => "bthrowing",
+            S::Grounded(G::UThrowing) => "uthrowing",
+            S::Grounded(G::DThrowing) => "dthrowing",
+            S::Grounded(G::Held) => "held",
+            S::Grounded(G::LetterThrowing) => "letter_throwing",
+            S::Aerial(A::Falling) => "falling",
+            S::Aerial(A::Jumping) => "jumping",
+            S::Aerial(A::NAiring) => "nairing",
+            S::Aerial(A::FAiring) => "fairing",
+            S::Aerial(A::BAiring) => "bairing",
+            S::Aerial(A::UAiring) => "uairing",
+            S::Aerial(A::DAiring) => "dairing",
+            S::Aerial(A::Tumbling) => "tumbling",
+            S::Aerial(A::LedgeHanging) => "ledge_hanging",
+            S::Aerial(A::AirDodging) => "air_dodging",
+            S::Aerial(A::Helpless) => "helpless",
+            S::Aerial(A::AirLetterThrowing) => "air_letter_throwing",
+        }
+    }
+}
+
+impl Default for Stance {
+    fn default() -> Self {
+        Stance::Grounded(GroundedStance::default())
+    }
+}
+
+pub type PostboxState = FighterState<Stance>;
+
+// Hitting just as the stick is flicked makes a smash attack, and
+// hitting with it held makes a tilt.
+fn grounded_attack(input: InputDiff) -> GroundedStance {
+    use self::GroundedStance as G;
+    match input.stick.flick() {
+        Some(Button::Up) => G::USmashCharging,
+        Some(Button::Down) => G::DSmashCharging,
+        Some(_) => G::FSmashCharging,
+        None if held(input, Button::Up) => G::UTilting,
+        None if held(input, Button::Down) => G::DTilting,
+        None if held_direction(input).is_some() => G::FTilting,
+        None => G::Jabbing,
+    }
+}
+
+// The smash attack a charging stance lets loose.
+fn smash_release(stance: GroundedStance) -> GroundedStance {
+    use self::GroundedStance as G;
+    match stance {
+        G::FSmashCharging => G::FSmashing,
+        G::USmashCharging => G::USmashing,
+        G::DSmashCharging => G::DSmashing,
+        _ => stance,
+    }
+}
+
+fn flicked_sideways(input: InputDiff) -> bool {
+    matches!(input.stick.flick(), Some(Button::Left | Button::Right))
+}
+
+fn aerial_attack(orientation: Orientation, input: InputDiff) -> AerialStance {
+    use self::AerialStance as A;
+    if held(input, Button::Up) {
+        A::UAiring
+    } else if held(input, Button::Down) {
+        A::DAiring
+    } else {
+        match held_direction(input) {
+            Some(direction) if direction == orientation => A::FAiring,
+            Some(_) => A::BAiring,
+            None => A::NAiring,
+        }
+    }
+}
+
+// What the fighter does first when it can act freely on the ground,
+// whether it is standing still or on the move.
+fn grounded_action(input: InputDiff) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    if pressed(input, Button::Jump) {
+        Some(G::JumpSquat)
+    } else if pressed(input, Button::Hit) && held(input, Button::Shield) {
+        Some(G::Grabbing)
+    } else if pressed(input, Button::Hit) {
+        Some(grounded_attack(input))
+    } else if pressed(input, Button::Special) {
+        Some(G::LetterThrowing)
+    } else if held(input, Button::Shield) {
+        Some(G::Shielding)
+    } else {
+        None
+    }
+}
+
+fn standing_input_map(input: InputDiff) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    grounded_action(input).or_else(|| {
+        if held(input, Button::Down) {
+            Some(G::Crouching)
+        } else if flicked_sideways(input) {
+            Some(G::Dashing)
+        } else if held_direction(input).is_some() {
+            Some(G::Walking)
+        } else {
+            None
+        }
+    })
+}
+
+fn walking_input_map(input: InputDiff) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    grounded_action(input).or_else(|| {
+        if held(input, Button::Down) {
+            Some(G::Crawling)
+        } else if flicked_sideways(input) {
+            Some(G::Dashing)
+        } else if held_direction(input).is_none() {
+            Some(G::Standing)
+        } else {
+            None
+        }
+    })
+}
+
+// A dash can be turned back on with another dash for as long as it
+// lasts, which is all dash-dancing is.
+fn dashing_input_map(orientation: Orientation, input: InputDiff) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    grounded_action(input).or_else(|| {
+        if held_direction(input).is_some_and(|direction| direction != orientation) {
+            Some(G::Dashing)
+        } else {
+            None
+        }
+    })
+}
+
+// Running fighters skid when they let go, and turn around
+// with a skid when they push the other way.
+fn running_input_map(orientation: Orientation, input: InputDiff) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    grounded_action(input).or_else(|| {
+        if held(input, Button::Down) {
+            Some(G::Crouching)
+        } else {
+            match held_direction(input) {
+                None => Some(G::Skidding),
+                Some(direction) if direction != orientation => Some(G::RunTurning),
+                Some(_) => None,
+            }
+        }
+    })
+}
+
+// Fighters can jump out of a skid, but nothing else.
+fn skidding_input_map(input: InputDiff) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    if pressed(input, Button::Jump) {
+        Some(G::JumpSquat)
+    } else {
+        None
+    }
+}
+
+fn crouching_input_map(input: InputDiff) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    if pressed(input, Button::Jump) {
+        Some(G::JumpSquat)
+    } else if pressed(input, Button::Hit) {
+        Some(grounded_attack(input))
+    } else if pressed(input, Button::Special) {
+        Some(G::LetterThrowing)
+    } else if held(input, Button::Shield) {
+        Some(G::Shielding)
+    } else if !held(input, Button::Down) {
+        Some(G::Standing)
+    } else if held_direction(input).is_some() {
+        Some(G::Crawling)
+    } else {
+        None
+    }
+}
+
+fn crawling_input_map(input: InputDiff) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    if pressed(input, Button::Jump) {
+        Some(G::JumpSquat)
+    } else if pressed(input, Button::Hit) {
+        Some(grounded_attack(input))
+    } else if pressed(input, Button::Special) {
+        Some(G::LetterThrowing)
+    } else if held(input, Button::Shield) {
+        Some(G::Shielding)
+    } else if !held(input, Button::Down) {
+        match held_direction(input) {
+            Some(_) => Some(G::Walking),
+            None => Some(G::Standing),
+        }
+    } else if held_direction(input).is_none() {
+        Some(G::Crouching)
+    } else {
+        None
+    }
+}
+
+// Smash attacks are let loose as soon as hit is let go, and
+// otherwise once they are fully charged.
+fn smash_charging_input_map(stance: GroundedStance, input: InputDiff) -> Option<GroundedStance> {
+    if held(input, Button::Hit) {
+        None
+    } else {
+        Some(smash_release(stance))
+    }
+}
+
+// Rolls go whichever way is pressed, and the fighter turns to face it.
+fn shielding_input_map(input: InputDiff) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    if pressed(input, Button::Jump) {
+        Some(G::JumpSquat)
+    } else if pressed(input, Button::Hit) {
+        Some(G::Grabbing)
+    } else if !held(input, Button::Shield) {
+        Some(G::Standing)
+    } else if pressed(input, Button::Down) {
+        Some(G::SpotDodging)
+    } else if held_direction(input).is_some()
+        && (pressed(input, Button::Left) || pressed(input, Button::Right))
+    {
+        Some(G::Rolling)
+    } else {
+        None
+    }
+}
+
+// Throws are forward or back relative to the way the holder faces.
+fn holding_input_map(orientation: Orientation, input: InputDiff) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    let direction = match (pressed(input, Button::Left), pressed(input, Button::Right)) {
+        (true, false) => Some(Orientation::Left),
+        (false, true) => Some(Orientation::Right),
+        _ => None,
+    };
+    if pressed(input, Button::Hit) {
+        Some(G::Pummeling)
+    } else if pressed(input, Button::Up) {
+        Some(G::UThrowing)
+    } else if pressed(input, Button::Down) {
+        Some(G::DThrowing)
+    } else {
+        direction.map(|direction| {
+            if direction == orientation {
+                G::FThrowing
+            } else {
+                G::BThrowing
+            }
+        })
+    }
+}
+
+// Shared by falling and jumping, which are the actionable aerial stances.
+fn airborne_input_map(
+    jumps: u8,
+    max_jumps: u8,
+    orientation: Orientation,
+    input: InputDiff,
+) -> Option<AerialStance> {
+    use self::AerialStance as A;
+    if pressed(input, Button::Jump) && jumps < max_jumps {
+        Some(A::Jumping)
+    } else if pressed(input, Button::Hit) {
+        Some(aerial_attack(orientation, input))
+    } else if pressed(input, Button::Special) {
+        Some(A::AirLetterThrowing)
+    } else if pressed(input, Button::Shield) {
+        Some(A::AirDodging)
+    } else {
+        None
+    }
+}
+
+fn grounded_user_input_map(
+    state: GroundedStance,
+    _frame: u8,
+    orientation: Orientation,
+    input: InputDiff,
+) -> Option<GroundedStance> {
+    use self::GroundedStance as G;
+    match state {
+        G::Standing => standing_input_map(input),
+        G::Walking => walking_input_map(input),
+        G::Dashing => dashing_input_map(orientation, input),
+        G::Running => running_input_map(orientation, input),
+        G::Skidding | G::RunTurning => skidding_input_map(input),
+        G::Crouching => crouching_input_map(input),
+        G::Crawling => crawling_input_map(input),
+        G::Shielding => shielding_input_map(input),
+        G::Holding => holding_input_map(orientation, input),
+        G::FSmashCharging | G::USmashCharging | G::DSmashCharging => {
+            smash_charging_input_map(state, input)
+        }
+        G::JumpSquat
+        | G::Jabbing
+        | G::FTilting
+        | G::UTilting
+        | G::DTilting
+        | G::FSmashing
+        | G::USmashing
+        | G::DSmashing
+        | G::LedgeClimbing
+        | G::ShieldStunned
+        | G::ShieldBroken
+        | G::SpotDodging
+        | G::Rolling
+        | G::Grabbing
+        | G::Pummeling
+        | G::FThrowing
+        | G::BThrowing
+        | G::UThrowing
+        | G::DThrowing
+        | G::Held
+        | G::LetterThrowing => None,
+    }
+}
+
+fn aerial_user_input_map(
+    state: AerialStance,
+    _frame: u8,
+    jumps: u8,
+    max_jumps: u8,
+    orientation: Orientation,
+    input: InputDiff,
+) -> Option<AerialStance> {
+    use self::AerialStance as A;
+    match state {
+        A::Falling | A::Jumping => airborne_input_map(jumps, max_jumps, orientation, input),
+        A::NAiring
+        | A::FAiring
+        | A::BAiring
+        | A::UAiring
+        | A::DAiring
+        | A::Tumbling
+        | A::LedgeHanging
+        | A::AirDodging
+        | A::Helpless
+        | A::AirLetterThrowing => None,
+    }
+}
+
+pub struct Postbox;
+
+impl Character for Postbox {
+    type Stance = Stance;
+
+    const NAME: &'static str = "postbox";
+    const HITSTUN: Stance = Stance::Aerial(AerialStance::Tumbling);
+    const SHIELD_STUN: Option<Stance> = Some(Stance::Grounded(GroundedStance::ShieldStunned));
+    const SHIELD_BREAK: Option<Stance> = Some(Stance::Grounded(GroundedStance::ShieldBroken));
+
+    fn frame_data(assets: &FrameDataAssets) -> &Handle<FrameDataTable> {
+        &assets.postbox
+    }
+
+    fn timeout_stance(stance: Stance) -> Stance {
+        use self::AerialStance as A;
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        match stance {
+            S::Grounded(G::JumpSquat) => S::Aerial(A::Jumping),
+            S::Grounded(G::Dashing | G::RunTurning) => S::Grounded(G::Running),
+            S::Grounded(G::ShieldStunned) => S::Grounded(G::Shielding),
+            S::Grounded(G::Pummeling) => S::Grounded(G::Holding),
+            S::Grounded(g @ (G::FSmashCharging | G::USmashCharging | G::DSmashCharging)) => {
+                S::Grounded(smash_release(g))
+            }
+            S::Aerial(A::AirDodging) => S::Aerial(A::Helpless),
+            S::Aerial(_) => S::Aerial(A::Falling),
+            S::Grounded(_) => S::Grounded(G::Standing),
+        }
+    }
+
+    fn environment_stance(stance: Stance, environment: Environment) -> Option<Stance> {
+        use self::AerialStance as A;
+        use self::Environment as E;
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        match (stance, environment) {
+            (S::Aerial(A::LedgeHanging), E::OnLedge) => None,
+            (_, E::OnLedge) => Some(S::Aerial(A::LedgeHanging)),
+            // Hitstun plays out in full even once the fighter is back down
+            (S::Aerial(A::Tumbling), E::Grounded) => None,
+            (S::Aerial(_), E::Grounded) => Some(S::Grounded(G::Standing)),
+            (S::Grounded(_) | S::Aerial(A::LedgeHanging), E::Airborne) => {
+                Some(S::Aerial(A::Falling))
+            }
+            _ => None,
+        }
+    }
+
+    // A grab only takes hold if it lands, and throws play out even
+    // if the held fighter gets away during them.
+    fn grab_stance(stance: Stance, tether: Tether) -> Option<Stance> {
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        match (stance, tether) {
+            (S::Grounded(G::Held), Tether::Held) => None,
+            (_, Tether::Held) => Some(S::Grounded(G::Held)),
+            (S::Grounded(G::Held), _) => Some(S::Grounded(G::Standing)),
+            (S::Grounded(G::Grabbing), Tether::Holding) => Some(S::Grounded(G::Holding)),
+            (S::Grounded(G::Holding | G::Pummeling), Tether::Free) => {
+                Some(S::Grounded(G::Standing))
+            }
+            _ => None,
+        }
+    }
+
+    fn input_map(
+        stance: Stance,
+        state: &PostboxState,
+        orientation: Orientation,
+        attributes: &Attributes,
+        input: InputDiff,
+    ) -> Option<Stance> {
+        use self::AerialStance as A;
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        let frame = state.countup;
+        match stance {
+            S::Aerial(A::LedgeHanging) => {
+                ledge_action(orientation, input).map(|action| match action {
+                    LedgeAction::Climb => S::Grounded(G::LedgeClimbing),
+                    LedgeAction::Jump => S::Aerial(A::Jumping),
+                    LedgeAction::Drop => S::Aerial(A::Falling),
+                })
+            }
+            S::Grounded(g) => {
+                grounded_user_input_map(g, frame, orientation, input).map(S::Grounded)
+            }
+            S::Aerial(a) => aerial_user_input_map(
+                a,
+                frame,
+                state.jumps,
+                attributes.max_aerial_jumps,
+                orientation,
+                input,
+            )
+            .map(S::Aerial),
+        }
+    }
+
+    // Fighters on the ground turn to face the direction being held
+    // while they are free to move, and as they start a dash, a run
+    // turnaround, a forward tilt, a forward smash or a roll.
+    fn can_turn(state: &PostboxState) -> bool {
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        match state.stance {
+            S::Grounded(G::Standing | G::Walking | G::Crouching | G::Crawling) => true,
+            S::Grounded(
+                G::Dashing | G::RunTurning | G::FTilting | G::FSmashCharging | G::Rolling,
+            ) => state.countup == 0,
+            _ => false,
+        }
+    }
+
+    fn can_drop_through(state: &PostboxState) -> bool {
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        matches!(
+            state.stance,
+            S::Grounded(G::Standing | G::Walking | G::Dashing | G::Running)
+        )
+    }
+
+    fn enter_stance(state: &mut PostboxState, new_stance: Stance) {
+        use self::AerialStance as A;
+        use self::GroundedStance as G;
+        use self::Stance as S;
+        // Smash attacks hit harder the longer they were charged
+        state.charge = match (state.stance, new_stance) {
+            (
+                S::Grounded(G::FSmashCharging | G::USmashCharging | G::DSmashCharging),
+                S::Grounded(G::FSmashing | G::USmashing | G::DSmashing),
+            ) => state.countup,
+            _ => 0,
+        };
+        // Grabbing a ledge gives the fighter its jumps back,
+        // and jumping from one doesn't use any up.
+        match (state.stance, new_stance) {
+            (S::Aerial(A::LedgeHanging), S::Aerial(A::Jumping)) => (),
+            (S::Aerial(_), S::Aerial(A::Jumping)) => state.jumps += 1,
+            (_, S::Grounded(_) | S::Aerial(A::LedgeHanging)) => state.jumps = 0,
+            _ => (),
+        }
+    }
+}
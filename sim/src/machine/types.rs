@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+#[derive(Component, Default, Reflect, Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Armour {
+    #[default]
+    None,
+    HyperArmour,
+    Invincibility,
+    // Hits take from the fighter's shield instead
+    Shield,
+}
+
+#[derive(Component, Default, Reflect, Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Physics {
+    #[default]
+    NotMoving,
+    Walking,
+    Dashing,
+    Running,
+    // Slows to a stop as traction takes hold
+    Skidding,
+    Crawling,
+    Rolling,
+    // Takes off from the ground or jumps again in the air
+    Jumping,
+    Falling,
+    Tumbling,
+    // Held in place on a ledge
+    Hanging,
+    // Pulled up from a ledge on to the platform
+    Climbing,
+    // Stands still with another fighter in its grasp
+    Holding,
+    // Carried in front of whoever is holding the fighter
+    Held,
+}
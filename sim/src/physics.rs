@@ -0,0 +1,398 @@
+use crate::world::{
+    Accelerating, Acceleration, Bounds, Fighter, Moving, Platform, PlatformId, Position,
+    StandingOn, Velocity,
+};
+use bevy::log;
+use bevy::prelude::*;
+
+use crate::world;
+
+// TODO: make this depend on character
+pub const TERMINAL_VELOCITY: i32 = 20;
+
+// sin(n degrees) * 1000, for n from 0 to 90.
+const SIN_TABLE: [i32; 91] = [
+    0, 17, 35, 52, 70, 87, 105, 122, 139, 156, 174, 191, 208, 225, 242, 259, 276, 292, 309, 326,
+    342, 358, 375, 391, 407, 423, 438, 454, 469, 485, 500, 515, 530, 545, 559, 574, 588, 602, 616,
+    629, 643, 656, 669, 682, 695, 707, 719, 731, 743, 755, 766, 777, 788, 799, 809, 819, 829, 839,
+    848, 857, 866, 875, 883, 891, 899, 906, 914, 921, 927, 934, 940, 946, 951, 956, 961, 966, 970,
+    974, 978, 982, 985, 988, 990, 993, 995, 996, 998, 999, 999, 1000, 1000,
+];
+
+// Trigonometry on whole degrees, scaled up by 1000, so that game
+// logic never depends on how a platform rounds floats.
+pub fn sin_permille(degrees: i32) -> i32 {
+    let d = degrees.rem_euclid(360) as usize;
+    match d {
+        0..=90 => SIN_TABLE[d],
+        91..=180 => SIN_TABLE[180 - d],
+        181..=270 => -SIN_TABLE[d - 180],
+        _ => -SIN_TABLE[360 - d],
+    }
+}
+
+pub fn cos_permille(degrees: i32) -> i32 {
+    sin_permille(degrees + 90)
+}
+
+fn first_collision(
+    position: &Position,
+    velocity: &Velocity,
+    platform_query: &Query<&Platform>,
+) -> Option<(PlatformId, Position)> {
+    let mut result: Option<(PlatformId, Position)> = None;
+    for platform in platform_query.iter() {
+        let mut collided = false;
+        let x_diff = velocity.x.signum();
+        let y_diff = velocity.y.signum();
+        if x_diff == 0 && y_diff == 0 {
+            continue;
+        }
+        let mut test_x = 0;
+        let mut test_y = 0;
+        if x_diff == 0 || y_diff == 0 {
+            log::trace!(
+                "Beginning collision loop with vars x_diff:{:?}, y_diff:{:?} and max velocity x:{:?}, y:{:?}",
+                x_diff,
+                y_diff,
+                velocity.x,
+                velocity.y,
+            );
+            loop {
+                if world::fighter_is_on_plat(
+                    &Position {
+                        x: position.x + test_x,
+                        y: position.y + test_y,
+                    },
+                    platform,
+                ) {
+                    collided = true;
+                    break;
+                }
+                test_x += x_diff;
+                test_y += y_diff;
+                if test_x.abs() > velocity.x.abs() || test_y.abs() > velocity.y.abs() {
+                    break;
+                }
+            }
+        } else {
+            log::trace!(
+                "Beginning collision loop with vars x_diff:{:?}, y_diff:{:?} and max velocity x:{:?}, y:{:?}",
+                x_diff,
+                y_diff,
+                velocity.x,
+                velocity.y,
+            );
+            loop {
+                if world::fighter_is_on_plat(
+                    &Position {
+                        x: position.x + test_x,
+                        y: position.y + test_y,
+                    },
+                    platform,
+                ) {
+                    collided = true;
+                    break;
+                }
+                if (test_y * velocity.x).abs() >= (velocity.y * test_x).abs() {
+                    test_x += x_diff;
+                } else {
+                    test_y += y_diff;
+                }
+                if test_x.abs() > velocity.x.abs() || test_y.abs() > velocity.y.abs() {
+                    break;
+                }
+            }
+        }
+        if !collided {
+            continue;
+        }
+        if let Some(ref pair) = result {
+            let pos = &pair.1;
+            if pos.x - position.x + pos.y - position.y > test_x + test_y {
+                result = Some((
+                    platform.id,
+                    Position {
+                        x: position.x + test_x,
+                        y: position.y + test_y,
+                    },
+                ));
+            }
+        } else {
+            result = Some((
+                platform.id,
+                Position {
+                    x: position.x + test_x,
+                    y: position.y + test_y,
+                },
+            ));
+        }
+    }
+    result
+}
+
+// Moves a fighter horizontally, stopping it against the side of the
+// first solid platform in its way.
+pub fn move_horizontally(position: &mut Position, velocity: &mut Velocity, solids: &[Bounds]) {
+    if velocity.x == 0 {
+        return;
+    }
+    let mut swept = world::fighter_body(position);
+    swept.width += velocity.x.abs();
+    if velocity.x < 0 {
+        swept.x += velocity.x;
+    }
+    let wall = solids
+        .iter()
+        .filter(|solid| swept.overlaps(solid))
+        .min_by_key(|solid| {
+            if velocity.x > 0 {
+                solid.x
+            } else {
+                -(solid.x + solid.width)
+            }
+        });
+    match wall {
+        Some(wall) => {
+            log::trace!("Player hit a wall");
+            position.x = if velocity.x > 0 {
+                wall.x - world::FIGHTER_DIMENSIONS
+            } else {
+                wall.x + wall.width
+            };
+            velocity.x = 0;
+        }
+        None => position.x += velocity.x,
+    }
+}
+
+// Moves a fighter vertically, stopping it against the underside of the
+// first solid platform above it. Returns the platform it landed on if
+// it came down on top of a solid platform.
+fn move_vertically(
+    position: &mut Position,
+    velocity: &mut Velocity,
+    solids: &[(PlatformId, Bounds)],
+) -> Option<PlatformId> {
+    if velocity.y == 0 {
+        return None;
+    }
+    let mut swept = world::fighter_body(position);
+    swept.height += velocity.y.abs();
+    if velocity.y < 0 {
+        swept.y += velocity.y;
+    }
+    let (platform, solid) = solids
+        .iter()
+        .filter(|(_, solid)| swept.overlaps(solid))
+        .min_by_key(|(_, solid)| {
+            if velocity.y > 0 {
+                solid.y
+            } else {
+                -(solid.y + solid.height)
+            }
+        })?;
+    if velocity.y > 0 {
+        log::trace!("Player hit a ceiling");
+        position.y = solid.y - world::FIGHTER_DIMENSIONS;
+        velocity.y = 0;
+        None
+    } else {
+        position.y = solid.y + solid.height + 1;
+        Some(*platform)
+    }
+}
+
+pub fn movement_system(
+    mut fighter_query: Query<
+        (Entity, &mut Position, &mut Velocity, Option<&StandingOn>),
+        (With<Moving>, With<Fighter>),
+    >,
+    platform_query: Query<&Platform>,
+    mut commands: Commands,
+) {
+    log::debug!("movement system beginning");
+    let solids: Vec<(PlatformId, Bounds)> = platform_query
+        .iter()
+        .filter_map(|platform| Some((platform.id, platform.solid_bounds()?)))
+        .collect();
+    let walls: Vec<Bounds> = solids.iter().map(|(_, solid)| *solid).collect();
+    for (fighter_entity, mut position, mut velocity, standing_on) in &mut fighter_query {
+        let mut landed_on = None;
+        if velocity.y > 0 {
+            if standing_on.is_some() {
+                log::trace!("Player left the ground");
+                commands.entity(fighter_entity).remove::<StandingOn>();
+            }
+        } else if velocity.y < 0 {
+            let first_col = first_collision(&position, &velocity, &platform_query);
+            if let Some((plat, col_position)) = first_col {
+                log::trace!("Player movement obstructed");
+                landed_on = Some(plat);
+                *position = col_position;
+            }
+        }
+        if landed_on.is_none() {
+            log::trace!("Player moving unobstructed");
+            move_horizontally(&mut position, &mut velocity, &walls);
+            landed_on = move_vertically(&mut position, &mut velocity, &solids);
+        }
+        if let Some(plat) = landed_on {
+            commands
+                .entity(fighter_entity)
+                .insert(StandingOn { platform: plat });
+        } else if let Some(standing_on) = standing_on.filter(|_| velocity.y <= 0) {
+            // Walking off the edge of a platform leaves the fighter in the air
+            let still_on = platform_query.iter().any(|platform| {
+                platform.id == standing_on.platform && platform.is_under(&position)
+            });
+            if !still_on {
+                log::trace!("Player walked off a platform");
+                commands.entity(fighter_entity).remove::<StandingOn>();
+            }
+        }
+        log::trace!("position is now {:?}", position);
+    }
+}
+
+pub fn acceleration_system(mut query: Query<(&mut Velocity, &Acceleration), With<Accelerating>>) {
+    log::debug!("acceleration system beginning");
+    for (mut velocity, acceleration) in &mut query {
+        velocity.x += acceleration.x;
+        velocity.y += acceleration.y;
+        log::trace!("velocity is now {:?}", velocity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless;
+    use crate::machine::postbox::{GroundedStance, PostboxState, Stance};
+    use crate::stage::StageId;
+
+    // Where the first player is, and whether they are standing on anything.
+    fn first_player(app: &mut App) -> (Position, bool) {
+        let entity = headless::fighter(app, 0);
+        let fighter = app.world.entity(entity);
+        (
+            fighter.get::<Position>().unwrap().clone(),
+            fighter.contains::<StandingOn>(),
+        )
+    }
+
+    // The first player's stance, and how fast they are going sideways.
+    fn first_stance(app: &mut App) -> (Stance, i32) {
+        let entity = headless::fighter(app, 0);
+        let fighter = app.world.entity(entity);
+        (
+            fighter.get::<PostboxState>().unwrap().stance,
+            fighter.get::<Velocity>().unwrap().x,
+        )
+    }
+
+    #[test]
+    fn walking_off_a_platform_starts_a_fall() {
+        let mut app = headless::scripted_app(StageId::Platform, "50 L -", 50);
+        let (position, standing) = first_player(&mut app);
+        assert!(!standing);
+        assert!(position.y < 0, "still at {position:?}");
+    }
+
+    #[test]
+    fn down_drops_through_soft_platforms() {
+        let mut app = headless::scripted_app(StageId::Platform, "30 - -\n1 D -", 45);
+        let (position, standing) = first_player(&mut app);
+        assert!(!standing);
+        assert!(position.y < 0, "still at {position:?}");
+    }
+
+    #[test]
+    fn solid_platforms_cannot_be_dropped_through() {
+        let mut app = headless::scripted_app(StageId::Battlefield, "30 - -\n1 D -", 45);
+        let (position, standing) = first_player(&mut app);
+        assert!(standing);
+        assert_eq!(position.y, 1);
+    }
+
+    #[test]
+    fn fighters_never_end_up_inside_solid_platforms() {
+        let mut app = headless::synctest_app(
+            &headless::TEST_FIGHTERS,
+            StageId::Battlefield,
+            InputSource::random(7),
+            2,
+        );
+        let mut platforms = app.world.query::<&Platform>();
+        let mut fighters = app.world.query_filtered::<&Position, With<Fighter>>();
+        for frame in 1..=600 {
+            headless::run_frames(&mut app, frame);
+            let solids: Vec<Bounds> = platforms
+                .iter(&app.world)
+                .filter_map(Platform::solid_bounds)
+                .collect();
+            for position in fighters.iter(&app.world) {
+                let body = world::fighter_body(position);
+                assert!(
+                    !solids.iter().any(|solid| body.overlaps(solid)),
+                    "fighter inside the stage at {position:?} on frame {frame}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn holding_a_direction_key_walks_at_full_speed() {
+        let mut app = headless::scripted_app(StageId::Battlefield, "30 - -\n20 R -", 50);
+        assert_eq!(
+            first_stance(&mut app),
+            (Stance::Grounded(GroundedStance::Walking), 3)
+        );
+    }
+
+    #[test]
+    fn dashes_turn_into_runs() {
+        let mut app = headless::scripted_app(StageId::Battlefield, "30 - -\n20 RF -", 50);
+        assert_eq!(
+            first_stance(&mut app),
+            (Stance::Grounded(GroundedStance::Running), 5)
+        );
+    }
+
+    #[test]
+    fn dashes_can_be_turned_back_on() {
+        let mut app = headless::scripted_app(StageId::Battlefield, "30 - -\n5 RF -\n5 LF -", 40);
+        assert_eq!(
+            first_stance(&mut app),
+            (Stance::Grounded(GroundedStance::Dashing), -6)
+        );
+    }
+
+    #[test]
+    fn letting_go_of_a_run_skids_to_a_stop() {
+        let mut app = headless::scripted_app(StageId::Battlefield, "30 - -\n20 RF -", 53);
+        let (stance, speed) = first_stance(&mut app);
+        assert_eq!(stance, Stance::Grounded(GroundedStance::Skidding));
+        assert!(0 < speed && speed < 5, "skidding at {speed}");
+
+        headless::run_frames(&mut app, 70);
+        assert_eq!(
+            first_stance(&mut app),
+            (Stance::Grounded(GroundedStance::Standing), 0)
+        );
+    }
+
+    #[test]
+    fn runs_turn_around_with_a_skid() {
+        let mut app = headless::scripted_app(StageId::Battlefield, "30 - -\n20 RF -\n20 LF -", 53);
+        let (stance, speed) = first_stance(&mut app);
+        assert_eq!(stance, Stance::Grounded(GroundedStance::RunTurning));
+        assert!(speed > 0, "skidding at {speed}");
+
+        headless::run_frames(&mut app, 70);
+        assert_eq!(
+            first_stance(&mut app),
+            (Stance::Grounded(GroundedStance::Running), -5)
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use crate::combat::strike;
+use crate::framedata::{FrameDataAssets, FrameDataTable, Hitbox};
+use crate::machine::types::Armour;
+use crate::machine::{Character, FighterState};
+use crate::shield::Shield;
+use crate::stage::Stage;
+use crate::world::{
+    Allegiance, Damage, Fighter, FrameCount, Hitstun, Hurtbox, Orientation, Position, Velocity,
+};
+use bevy::log;
+use bevy::prelude::*;
+use bevy_ggrs::AddRollbackCommandExtension;
+
+// Everything about a projectile besides who threw it, which way and
+// where it is. All of it is registered for rollback, so a projectile
+// which was destroyed comes back whole when a rollback restores it.
+#[derive(Component, Reflect, Default, Debug, Hash, Clone)]
+pub struct Projectile {
+    pub hitbox: Hitbox,
+    pub lifetime: u8,
+    pub age: u8,
+    // The frame it was thrown on, and which of the stance's
+    // projectiles it is, so that projectiles can be put in the
+    // same order on every machine.
+    pub thrown: u32,
+    pub index: u8,
+}
+
+// The order projectiles are visited in, the same on every machine.
+pub fn projectile_order(allegiance: &Allegiance, projectile: &Projectile) -> (usize, u32, u8) {
+    (allegiance.handle.0, projectile.thrown, projectile.index)
+}
+
+fn mirror(x: i32, orientation: Orientation) -> i32 {
+    match orientation {
+        Orientation::Right => x,
+        Orientation::Left => -x,
+    }
+}
+
+// Spawns a projectile, registered for rollback like a fighter is.
+pub fn spawn_projectile(
+    commands: &mut Commands,
+    projectile: Projectile,
+    owner: Allegiance,
+    orientation: Orientation,
+    position: Position,
+    velocity: Velocity,
+) -> Entity {
+    commands
+        .spawn((projectile, owner, orientation, position, velocity))
+        .add_rollback()
+        .id()
+}
+
+// Throws out the projectiles of fighters playing as `C` which have
+// reached the frame of their stance that throws one.
+pub fn spawn_projectile_system<C: Character>(
+    mut commands: Commands,
+    frame: Res<FrameCount>,
+    frame_data: Res<FrameDataAssets>,
+    tables: Res<Assets<FrameDataTable>>,
+    query: Query<(
+        &Allegiance,
+        &FighterState<C::Stance>,
+        &Orientation,
+        &Position,
+    )>,
+) {
+    log::debug!("{} projectile spawn system beginning", C::NAME);
+    let table = tables
+        .get(C::frame_data(&frame_data))
+        .expect("frame data is loaded before the game starts");
+    for (allegiance, state, orientation, position) in query.iter() {
+        let projectiles = table.get(state.stance).projectiles.iter().enumerate();
+        for (index, spawn) in projectiles.filter(|(_, spawn)| spawn.frame == state.countup) {
+            log::trace!(
+                "Player {:?} threw a projectile from {:?}",
+                allegiance.handle,
+                state.stance
+            );
+            spawn_projectile(
+                &mut commands,
+                Projectile {
+                    hitbox: spawn.hitbox,
+                    lifetime: spawn.lifetime,
+                    age: 0,
+                    thrown: frame.0,
+                    index: index as u8,
+                },
+                allegiance.clone(),
+                *orientation,
+                Position {
+                    x: position.x + mirror(spawn.x, *orientation),
+                    y: position.y + spawn.y,
+                },
+                Velocity {
+                    x: mirror(spawn.velocity_x, *orientation),
+                    y: spawn.velocity_y,
+                },
+            );
+        }
+    }
+}
+
+type ProjectileQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Projectile,
+        (&'static Allegiance, &'static Orientation),
+        (&'static mut Position, &'static Velocity),
+    ),
+    Without<Fighter>,
+>;
+
+type TargetQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        (&'static Allegiance, &'static Position),
+        &'static Hurtbox,
+        (
+            &'static mut Damage,
+            &'static mut Velocity,
+            &'static mut Hitstun,
+            &'static mut Shield,
+            &'static Armour,
+        ),
+    ),
+    With<Fighter>,
+>;
+
+// Moves every projectile along, and destroys those which have run out
+// of time, left the blast zone or hit someone other than whoever threw
+// them. Projectiles pass through invincible fighters. Projectiles and
+// fighters are both visited in a fixed order, so that two projectiles
+// hitting on the same frame land in the same order on every machine.
+pub fn projectile_system(
+    mut commands: Commands,
+    stage: Res<Stage>,
+    mut projectile_query: ProjectileQuery,
+    mut target_query: TargetQuery,
+) {
+    log::debug!("projectile system beginning");
+    let mut projectiles: Vec<_> = projectile_query.iter_mut().collect();
+    projectiles.sort_by_key(|(_, projectile, (allegiance, _), _)| {
+        projectile_order(allegiance, projectile)
+    });
+    let mut targets: Vec<(Entity, usize)> = target_query
+        .iter()
+        .map(|(entity, (allegiance, _), _, _)| (entity, allegiance.handle.0))
+        .collect();
+    targets.sort_by_key(|(_, handle)| *handle);
+
+    for (entity, mut projectile, (allegiance, orientation), (mut position, velocity)) in projectiles
+    {
+        position.x += velocity.x;
+        position.y += velocity.y;
+        let age = projectile.age;
+        projectile.age = age.saturating_add(1);
+        if age >= projectile.lifetime || !stage.blast_zone.contains(&position) {
+            log::trace!("Projectile {:?} is gone", entity);
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if !projectile.hitbox.frames.contains(age) {
+            continue;
+        }
+        let hitbox_bounds = projectile.hitbox.bounds.at(&position, *orientation);
+        let hit = targets.iter().find(|(target, handle)| {
+            let (_, (_, target_position), hurtbox, (_, _, _, _, armour)) =
+                target_query.get(*target).unwrap();
+            *handle != allegiance.handle.0
+                && *armour != Armour::Invincibility
+                && hitbox_bounds.overlaps(&hurtbox.at(target_position))
+        });
+        if let Some((target, handle)) = hit {
+            log::debug!(
+                "Player {:?}'s projectile hit player {handle}",
+                allegiance.handle
+            );
+            let (_, _, _, defence) = target_query.get_mut(*target).unwrap();
+            strike(
+                &mut commands,
+                *target,
+                &projectile.hitbox,
+                *orientation,
+                defence,
+            );
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless;
+
+    // Stands the fighters on Battlefield's main platform, the first
+    // at 0 facing right.
+    fn stand(app: &mut App, second_x: i32) {
+        headless::stand(app, 0, 0, Orientation::Right);
+        headless::stand(app, 1, second_x, Orientation::Right);
+    }
+
+    fn projectiles(app: &mut App) -> usize {
+        let mut query = app.world.query::<&Projectile>();
+        query.iter(&app.world).count()
+    }
+
+    fn percent(app: &mut App, handle: usize) -> u16 {
+        let entity = headless::fighter(app, handle);
+        app.world.get::<Damage>(entity).unwrap().percent
+    }
+
+    #[test]
+    fn letters_hit_whoever_is_in_front_and_disappear() {
+        let mut app = headless::test_app();
+        headless::step(&mut app, 60, &["-"]);
+        stand(&mut app, 200);
+        headless::step(&mut app, 1, &["B"]);
+        headless::step(&mut app, 14, &["-"]);
+        assert_eq!(projectiles(&mut app), 1);
+
+        headless::step(&mut app, 20, &["-"]);
+        assert_eq!(projectiles(&mut app), 0);
+        assert_eq!(percent(&mut app, 1), 4);
+        assert_eq!(percent(&mut app, 0), 0);
+    }
+
+    #[test]
+    fn letters_run_out_without_hitting_anyone() {
+        let mut app = headless::test_app();
+        headless::step(&mut app, 60, &["-"]);
+        stand(&mut app, -200);
+        headless::step(&mut app, 1, &["B"]);
+        headless::step(&mut app, 60, &["-"]);
+        assert_eq!(projectiles(&mut app), 1);
+
+        headless::step(&mut app, 20, &["-"]);
+        assert_eq!(projectiles(&mut app), 0);
+        assert_eq!(percent(&mut app, 1), 0);
+    }
+}
@@ -0,0 +1,321 @@
+use crate::input::CombinedInput;
+use crate::intent::FrameInputs;
+use crate::snapshot::Snapshot;
+use crate::stage::StageId;
+use crate::world::{CharacterId, FrameCount};
+use bevy::log;
+use bevy::prelude::*;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"FGRP";
+// Bumped whenever the layout of the file changes.
+const FORMAT_VERSION: u16 = 2;
+// Replays only play back the same on the game logic they were
+// recorded on, so they are stamped with its version.
+pub const GAME_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Everything needed to play a match back: who fought as what, where,
+// and every player's input on every frame.
+//
+// On disk, after the magic number and format version, strings are
+// prefixed with their length as a u8, and inputs are stored as runs
+// of identical frames, each a u16 count followed by three bytes per
+// player: their buttons, then where their stick is across and up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub game_version: String,
+    pub characters: Vec<CharacterId>,
+    pub stage: StageId,
+    // Indexed by frame, then by player handle.
+    pub inputs: Vec<Vec<CombinedInput>>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    NotAReplay,
+    UnsupportedFormat(u16),
+    UnknownCharacter(u8),
+    UnknownStage(String),
+    InvalidString,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ReplayError as E;
+        match self {
+            E::Io(err) => write!(f, "could not read replay: {err}"),
+            E::NotAReplay => write!(f, "not a replay file"),
+            E::UnsupportedFormat(version) => {
+                write!(f, "replay format {version} is not supported")
+            }
+            E::UnknownCharacter(id) => write!(f, "unknown character {id}"),
+            E::UnknownStage(name) => write!(f, "unknown stage `{name}`"),
+            E::InvalidString => write!(f, "replay contains a string which is not UTF-8"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+fn write_str(writer: &mut impl Write, s: &str) -> io::Result<()> {
+    let len = u8::try_from(s.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "string too long for replay"))?;
+    writer.write_all(&[len])?;
+    writer.write_all(s.as_bytes())
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_str(reader: &mut impl Read) -> Result<String, ReplayError> {
+    let mut buf = vec![0; read_u8(reader)? as usize];
+    reader.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|_| ReplayError::InvalidString)
+}
+
+impl Replay {
+    pub fn new(characters: Vec<CharacterId>, stage: StageId) -> Self {
+        Replay {
+            game_version: GAME_VERSION.to_owned(),
+            characters,
+            stage,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        write_str(writer, &self.game_version)?;
+        writer.write_all(&[self.characters.len() as u8])?;
+        for character in &self.characters {
+            writer.write_all(&[character.index() as u8])?;
+        }
+        write_str(writer, self.stage.name())?;
+        for run in self.inputs.chunk_by(|a, b| a == b) {
+            for chunk in run.chunks(u16::MAX as usize) {
+                writer.write_all(&(chunk.len() as u16).to_le_bytes())?;
+                writer.write_all(bytemuck::cast_slice(&chunk[0]))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read(reader: &mut impl Read) -> Result<Self, ReplayError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(ReplayError::NotAReplay);
+        }
+        let format = read_u16(reader)?;
+        if format != FORMAT_VERSION {
+            return Err(ReplayError::UnsupportedFormat(format));
+        }
+        let game_version = read_str(reader)?;
+        if game_version != GAME_VERSION {
+            log::warn!(
+                "Replay was recorded on version {game_version} \
+                 and may not play back the same on {GAME_VERSION}"
+            );
+        }
+        let players = read_u8(reader)? as usize;
+        let characters = (0..players)
+            .map(|_| {
+                let id = read_u8(reader)?;
+                CharacterId::ALL
+                    .get(id as usize)
+                    .copied()
+                    .ok_or(ReplayError::UnknownCharacter(id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let stage = read_str(reader)?;
+        let stage = StageId::from_name(&stage).ok_or(ReplayError::UnknownStage(stage))?;
+
+        let mut inputs = Vec::new();
+        let mut count = [0; 2];
+        loop {
+            // The file ends after a whole run
+            match reader.read(&mut count[..1])? {
+                0 => break,
+                _ => reader.read_exact(&mut count[1..])?,
+            }
+            let mut frame = vec![CombinedInput::new(); players];
+            reader.read_exact(bytemuck::cast_slice_mut(&mut frame))?;
+            inputs.extend(std::iter::repeat(frame).take(u16::from_le_bytes(count) as usize));
+        }
+        Ok(Replay {
+            game_version,
+            characters,
+            stage,
+            inputs,
+        })
+    }
+}
+
+// Records the inputs of the match being played.
+#[derive(Resource)]
+pub struct ReplayRecorder(pub Replay);
+
+// Runs in the GgrsSchedule. A frame which is resimulated after a
+// rollback replaces what was recorded for it, so once every frame
+// is confirmed the recording holds the inputs which were really used.
+pub fn record_system(
+    frame: Res<FrameCount>,
+    inputs: Res<FrameInputs>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    let recorded = &mut recorder.0.inputs;
+    recorded.truncate(frame.0 as usize - 1);
+    recorded.push(inputs.0.clone());
+}
+
+// Frames between the snapshots a ReplayPlayer keeps.
+pub const SNAPSHOT_INTERVAL: u32 = 60;
+
+// Steps through a replay outside of GGRS, one frame at a time. A snapshot
+// is kept every SNAPSHOT_INTERVAL frames, so seeking backwards only has to
+// re-simulate from the nearest one before the frame sought.
+#[derive(Resource)]
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    snapshots: BTreeMap<u32, Snapshot>,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer {
+            replay,
+            snapshots: BTreeMap::new(),
+        }
+    }
+
+    // How many frames the replay lasts.
+    pub fn frames(&self) -> u32 {
+        self.replay.inputs.len() as u32
+    }
+
+    // Simulates the next frame, unless the replay has ended.
+    pub fn step(&mut self, world: &mut World) -> bool {
+        let frame = world.resource::<FrameCount>().0;
+        let Some(inputs) = self.replay.inputs.get(frame as usize) else {
+            return false;
+        };
+        if frame % SNAPSHOT_INTERVAL == 0 && !self.snapshots.contains_key(&frame) {
+            self.snapshots.insert(frame, Snapshot::take(world));
+        }
+        crate::step_frame(world, inputs.clone());
+        true
+    }
+
+    // Leaves the world as it was at the end of the frame, or at the end
+    // of the replay if that comes first.
+    pub fn seek(&mut self, world: &mut World, frame: u32) {
+        let frame = frame.min(self.frames());
+        if frame < world.resource::<FrameCount>().0 {
+            let (_, snapshot) = self
+                .snapshots
+                .range(..=frame)
+                .next_back()
+                .expect("the first frame is snapshotted before it is stepped past");
+            snapshot.restore(world);
+        }
+        while world.resource::<FrameCount>().0 < frame {
+            self.step(world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::Checksum;
+    use crate::headless;
+    use crate::input::InputSource;
+    use bevy_ggrs::GgrsSchedule;
+
+    #[test]
+    fn replays_survive_a_round_trip() {
+        let mut replay = Replay::new(
+            vec![CharacterId::Postbox, CharacterId::Celeste],
+            StageId::Battlefield,
+        );
+        let InputSource::Script { frames, .. } =
+            InputSource::parse_script("70000 R L\n1 RH J\n3 - -").unwrap()
+        else {
+            unreachable!()
+        };
+        replay.inputs = frames;
+
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+        assert_eq!(Replay::read(&mut bytes.as_slice()).unwrap(), replay);
+    }
+
+    // Records a sync tested match, returning the replay and the
+    // checksum of its last frame.
+    fn record(frames: u32) -> (Replay, Checksum) {
+        let characters = [CharacterId::Celeste, CharacterId::Postbox];
+        let mut app =
+            headless::synctest_app(&characters, StageId::Battlefield, InputSource::random(3), 2);
+        app.insert_resource(ReplayRecorder(Replay::new(
+            characters.to_vec(),
+            StageId::Battlefield,
+        )))
+        .add_systems(
+            GgrsSchedule,
+            record_system.after(crate::checksum::checksum_system),
+        );
+        headless::run_frames(&mut app, frames);
+        let checksum = *app.world.resource::<Checksum>();
+        let replay = app.world.resource::<ReplayRecorder>().0.clone();
+        assert_eq!(replay.inputs.len(), frames as usize);
+        (replay, checksum)
+    }
+
+    #[test]
+    fn replays_play_back_the_same() {
+        let (replay, checksum) = record(300);
+        let mut app = headless::replay_app(&replay);
+        headless::run_frames(&mut app, 300);
+        assert_eq!(*app.world.resource::<Checksum>(), checksum);
+    }
+
+    #[test]
+    fn seeking_a_replay_lands_on_the_same_frame() {
+        let (replay, checksum) = record(300);
+        let mut app = headless::viewer_app(&replay);
+        app.update();
+        app.world
+            .resource_scope(|world, mut player: Mut<ReplayPlayer>| {
+                player.seek(world, 300);
+                assert_eq!(*world.resource::<Checksum>(), checksum);
+                player.seek(world, 137);
+                let middle = *world.resource::<Checksum>();
+                assert_eq!(world.resource::<FrameCount>().0, 137);
+                player.seek(world, 1000);
+                assert_eq!(*world.resource::<Checksum>(), checksum);
+                assert!(!player.step(world));
+                player.seek(world, 137);
+                assert_eq!(*world.resource::<Checksum>(), middle);
+            });
+    }
+}
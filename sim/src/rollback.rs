@@ -0,0 +1,96 @@
+use crate::grab::{HeldBy, Holding};
+use crate::machine::celeste::CelesteState;
+use crate::machine::postbox::PostboxState;
+use crate::machine::types::{Armour, Physics};
+use crate::shield::Shield;
+use crate::types::GgrsConfig;
+use crate::world::{
+    Acceleration, Allegiance, Damage, HangingFrom, HitRegistry, Hitstun, InputBuffer, InputDiff,
+    LedgeCooldown, Orientation, Position, StandingOn, Stocks, Velocity,
+};
+use bevy::ecs::query::ROQueryItem;
+use bevy::ecs::world::EntityMut;
+use bevy_ggrs::GgrsPlugin;
+
+// Declares everything that's done with the list of a fighter's rolled
+// back components below, so that none of it can leave one out.
+macro_rules! fighter_components {
+    (
+        $([$($field:ident: $component:ty),* $(,)?])*
+        optional [$($optional:ident: $optional_component:ty),* $(,)?]
+    ) => {
+        // An owned copy of every rolled back component of one fighter.
+        #[derive(Clone, Debug, Hash)]
+        pub struct FighterRecord {
+            $($(pub $field: $component,)*)*
+            $(pub $optional: Option<$optional_component>,)*
+        }
+
+        // Reads every rolled back component of a fighter. They are
+        // grouped into nested tuples as a query tuple can only hold 15.
+        pub type FighterQuery = (
+            $(($(&'static $component,)*),)*
+            ($(Option<&'static $optional_component>,)*),
+        );
+
+        impl FighterRecord {
+            pub fn new(item: ROQueryItem<'_, FighterQuery>) -> Self {
+                let ($(($($field,)*),)* ($($optional,)*),) = item;
+                FighterRecord {
+                    $($($field: Clone::clone($field),)*)*
+                    $($optional: $optional.cloned(),)*
+                }
+            }
+
+            // Puts the fighter's components back on its entity,
+            // removing those it didn't have.
+            pub fn restore(&self, entity: &mut EntityMut) {
+                entity.insert(($(($(Clone::clone(&self.$field),)*),)*));
+                $(match &self.$optional {
+                    Some(component) => entity.insert(component.clone()),
+                    None => entity.remove::<$optional_component>(),
+                };)*
+            }
+        }
+
+        pub fn register_fighter_components(
+            plugin: GgrsPlugin<GgrsConfig>,
+        ) -> GgrsPlugin<GgrsConfig> {
+            plugin
+                $($(.register_rollback_component::<$component>())*)*
+                $(.register_rollback_component::<$optional_component>())*
+        }
+    };
+}
+
+// Every component of a fighter which changes from frame to frame.
+// GGRS rolls them back, checksums hash them and snapshots copy them.
+fighter_components! {
+    [
+        allegiance: Allegiance,
+        input_diff: InputDiff,
+        input_buffer: InputBuffer,
+        orientation: Orientation,
+    ]
+    [
+        position: Position,
+        velocity: Velocity,
+        acceleration: Acceleration,
+        ledge_cooldown: LedgeCooldown,
+    ]
+    [
+        hitstun: Hitstun,
+        physics: Physics,
+        armour: Armour,
+        hit_registry: HitRegistry,
+    ]
+    [stocks: Stocks, damage: Damage, shield: Shield]
+    optional [
+        standing_on: StandingOn,
+        hanging_from: HangingFrom,
+        holding: Holding,
+        held_by: HeldBy,
+        postbox: PostboxState,
+        celeste: CelesteState,
+    ]
+}
@@ -0,0 +1,135 @@
+use crate::machine::types::Armour;
+use bevy::log;
+use bevy::prelude::*;
+
+pub const SHIELD_MAX: i32 = 1000;
+// Lost every frame the shield is held up, and won back every frame
+// it isn't.
+const SHIELD_DRAIN: i32 = 3;
+const SHIELD_REGEN: i32 = 2;
+// Lost for every point of damage a hit on the shield would have done.
+const SHIELD_DAMAGE: i32 = 25;
+// A broken shield comes back at a third of its health.
+const BROKEN_SHIELD_HEALTH: i32 = SHIELD_MAX / 3;
+
+// How much more the fighter's shield can take before it breaks. Hits
+// on the shield stun the fighter for `stun` frames, or break it, which
+// the fighter's state machine picks up on its next frame.
+#[derive(Component, Reflect, Debug, Hash, Clone)]
+pub struct Shield {
+    pub health: i32,
+    pub stun: i8,
+    pub broken: bool,
+}
+
+impl Default for Shield {
+    fn default() -> Self {
+        Shield {
+            health: SHIELD_MAX,
+            stun: 0,
+            broken: false,
+        }
+    }
+}
+
+impl Shield {
+    pub fn block(&mut self, damage: u16) {
+        self.health -= damage as i32 * SHIELD_DAMAGE;
+        if self.health <= 0 {
+            self.break_shield();
+        } else {
+            self.stun = (damage as i32 / 2 + 2).min(i8::MAX as i32) as i8;
+        }
+    }
+
+    fn break_shield(&mut self) {
+        log::trace!("Shield broken");
+        self.health = BROKEN_SHIELD_HEALTH;
+        self.stun = 0;
+        self.broken = true;
+    }
+}
+
+// Fighters whose stance has their shield up have it drained,
+// and everyone else's shield regenerates.
+pub fn shield_system(mut query: Query<(&Armour, &mut Shield)>) {
+    log::debug!("shield system beginning");
+    for (armour, mut shield) in query.iter_mut() {
+        if *armour == Armour::Shield {
+            shield.health -= SHIELD_DRAIN;
+            if shield.health <= 0 {
+                shield.break_shield();
+            }
+        } else {
+            shield.health = (shield.health + SHIELD_REGEN).min(SHIELD_MAX);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless;
+    use crate::machine::postbox::{GroundedStance, PostboxState, Stance};
+    use crate::world::Position;
+
+    #[test]
+    fn blocked_hits_stun_and_then_break_the_shield() {
+        let mut shield = Shield::default();
+        shield.block(10);
+        assert_eq!(shield.health, SHIELD_MAX - 10 * SHIELD_DAMAGE);
+        assert_eq!(shield.stun, 7);
+        assert!(!shield.broken);
+
+        shield.block(40);
+        assert!(shield.broken);
+        assert_eq!(shield.health, BROKEN_SHIELD_HEALTH);
+        assert_eq!(shield.stun, 0);
+    }
+
+    // Two Postboxes standing on Battlefield, with the first one
+    // pressing `letters` after they have landed.
+    fn play(letters: &[(u32, &str)]) -> App {
+        let mut app = headless::landed_app();
+        headless::play(&mut app, letters);
+        app
+    }
+
+    fn first_player(app: &mut App) -> (PostboxState, Shield, Position) {
+        let entity = headless::fighter(app, 0);
+        let shield = app.world.get::<Shield>(entity).unwrap().clone();
+        let position = app.world.get::<Position>(entity).unwrap().clone();
+        (headless::postbox_state(app, 0), shield, position)
+    }
+
+    #[test]
+    fn holding_shield_drains_it_and_letting_go_refills_it() {
+        let mut app = play(&[(60, "S")]);
+        let (state, shield, _) = first_player(&mut app);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::Shielding));
+        assert!(shield.health < SHIELD_MAX);
+
+        let held = shield.health;
+        let mut app = play(&[(60, "S"), (30, "-")]);
+        let (state, shield, _) = first_player(&mut app);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::Standing));
+        assert!(shield.health > held);
+    }
+
+    #[test]
+    fn holding_shield_too_long_breaks_it() {
+        let frames = (SHIELD_MAX / SHIELD_DRAIN) as u32 + 2;
+        let mut app = play(&[(frames, "S")]);
+        let (state, _, _) = first_player(&mut app);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::ShieldBroken));
+    }
+
+    #[test]
+    fn rolls_go_the_way_pressed() {
+        let (_, _, start) = first_player(&mut play(&[]));
+        let mut app = play(&[(5, "S"), (1, "SL"), (30, "S")]);
+        let (state, _, position) = first_player(&mut app);
+        assert_eq!(state.stance, Stance::Grounded(GroundedStance::Shielding));
+        assert!(position.x < start.x);
+    }
+}
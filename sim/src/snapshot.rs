@@ -0,0 +1,173 @@
+use crate::checksum::Checksum;
+use crate::projectile::{spawn_projectile, Projectile};
+use crate::rollback::{FighterQuery, FighterRecord};
+use crate::stage::Stage;
+use crate::world::{
+    spawn_fighter, Allegiance, CharacterId, Fighter, FrameCount, Orientation, Position, Velocity,
+};
+use bevy::ecs::system::CommandQueue;
+use bevy::log;
+use bevy::prelude::*;
+
+#[derive(Clone)]
+struct ProjectileSnapshot {
+    projectile: Projectile,
+    owner: Allegiance,
+    orientation: Orientation,
+    position: Position,
+    velocity: Velocity,
+}
+
+// A copy of the simulation as of the end of a frame, which can be
+// restored to re-simulate from that frame without GGRS. Unlike the
+// snapshots GGRS keeps for rolling back, these are kept as long as
+// the caller likes.
+#[derive(Clone)]
+pub struct Snapshot {
+    pub frame: FrameCount,
+    checksum: Checksum,
+    fighters: Vec<FighterRecord>,
+    projectiles: Vec<ProjectileSnapshot>,
+}
+
+impl Snapshot {
+    pub fn take(world: &mut World) -> Self {
+        let mut query = world.query_filtered::<FighterQuery, With<Fighter>>();
+        let fighters = query.iter(world).map(FighterRecord::new).collect();
+        let mut query =
+            world.query::<(&Projectile, &Allegiance, &Orientation, &Position, &Velocity)>();
+        let projectiles = query
+            .iter(world)
+            .map(
+                |(projectile, owner, orientation, position, velocity)| ProjectileSnapshot {
+                    projectile: projectile.clone(),
+                    owner: owner.clone(),
+                    orientation: *orientation,
+                    position: position.clone(),
+                    velocity: velocity.clone(),
+                },
+            )
+            .collect();
+        Snapshot {
+            frame: *world.resource::<FrameCount>(),
+            checksum: *world.resource::<Checksum>(),
+            fighters,
+            projectiles,
+        }
+    }
+
+    // Puts the world back as it was. Fighters which have been knocked
+    // out since, and projectiles, are spawned again and are left for
+    // the caller to draw.
+    pub fn restore(&self, world: &mut World) {
+        log::debug!("Restoring the snapshot of frame {:?}", self.frame);
+        world.insert_resource(self.frame);
+        world.insert_resource(self.checksum);
+
+        let mut query = world.query_filtered::<(Entity, &Allegiance), With<Fighter>>();
+        let existing: Vec<_> = query
+            .iter(world)
+            .map(|(entity, allegiance)| (allegiance.handle, entity))
+            .collect();
+        for (handle, entity) in &existing {
+            if !self
+                .fighters
+                .iter()
+                .any(|fighter| fighter.allegiance.handle == *handle)
+            {
+                world.despawn(*entity);
+            }
+        }
+
+        for fighter in &self.fighters {
+            let entity = match existing
+                .iter()
+                .find(|(handle, _)| *handle == fighter.allegiance.handle)
+            {
+                Some((_, entity)) => *entity,
+                None => {
+                    let character = if fighter.celeste.is_some() {
+                        CharacterId::Celeste
+                    } else {
+                        CharacterId::Postbox
+                    };
+                    let stage = world.resource::<Stage>().clone();
+                    let mut queue = CommandQueue::default();
+                    let entity = spawn_fighter(
+                        &mut Commands::new(&mut queue, world),
+                        &stage,
+                        fighter.allegiance.handle,
+                        character,
+                    );
+                    queue.apply(world);
+                    entity
+                }
+            };
+            fighter.restore(&mut world.entity_mut(entity));
+        }
+
+        // Projectiles come and go too quickly to be worth matching up,
+        // so they are all thrown away and spawned again.
+        let mut query = world.query_filtered::<Entity, With<Projectile>>();
+        let thrown: Vec<_> = query.iter(world).collect();
+        for entity in thrown {
+            world.despawn(entity);
+        }
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, world);
+        for projectile in &self.projectiles {
+            spawn_projectile(
+                &mut commands,
+                projectile.projectile.clone(),
+                projectile.owner.clone(),
+                projectile.orientation,
+                projectile.position.clone(),
+                projectile.velocity.clone(),
+            );
+        }
+        queue.apply(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::checksum_system;
+    use crate::headless;
+
+    fn projectiles(app: &mut App) -> usize {
+        let mut query = app.world.query::<&Projectile>();
+        query.iter(&app.world).count()
+    }
+
+    // Checksums the world as it is now, rather than reading back
+    // the checksum of the last frame to be simulated.
+    fn checksum(app: &mut App) -> Checksum {
+        let mut schedule = Schedule::default();
+        schedule.add_systems(checksum_system);
+        schedule.run(&mut app.world);
+        *app.world.resource::<Checksum>()
+    }
+
+    #[test]
+    fn restoring_puts_back_fighters_and_projectiles() {
+        let mut app = headless::landed_app();
+        headless::step(&mut app, 1, &["B"]);
+        headless::step(&mut app, 14, &["-"]);
+        assert_eq!(projectiles(&mut app), 1);
+        let snapshot = Snapshot::take(&mut app.world);
+        let taken = checksum(&mut app);
+
+        headless::step(&mut app, 70, &["RH", "L"]);
+        let stepped = checksum(&mut app);
+        assert_eq!(projectiles(&mut app), 0);
+        assert_ne!(stepped, taken);
+
+        snapshot.restore(&mut app.world);
+        assert_eq!(projectiles(&mut app), 1);
+        assert_eq!(checksum(&mut app), taken);
+
+        headless::step(&mut app, 70, &["RH", "L"]);
+        assert_eq!(checksum(&mut app), stepped);
+    }
+}
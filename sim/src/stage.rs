@@ -0,0 +1,314 @@
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::log;
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use bevy_asset_loader::prelude::*;
+use serde::Deserialize;
+
+use std::fmt;
+
+use crate::types::MAX_PLAYERS;
+use crate::world::{Bounds, Orientation, PlatformKind, Position};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum StageId {
+    #[default]
+    Platform,
+    Battlefield,
+}
+
+impl StageId {
+    pub const ALL: [StageId; 2] = [StageId::Platform, StageId::Battlefield];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StageId::Platform => "platform",
+            StageId::Battlefield => "battlefield",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|stage| stage.name().eq_ignore_ascii_case(name))
+    }
+}
+
+// The stage the match is played on.
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct StageChoice(pub StageId);
+
+#[derive(AssetCollection, Resource)]
+pub struct StageAssets {
+    #[asset(path = "stages/platform.stage.yaml")]
+    pub platform: Handle<Stage>,
+    #[asset(path = "stages/battlefield.stage.yaml")]
+    pub battlefield: Handle<Stage>,
+}
+
+impl StageAssets {
+    pub fn get(&self, stage: StageId) -> &Handle<Stage> {
+        match stage {
+            StageId::Platform => &self.platform,
+            StageId::Battlefield => &self.battlefield,
+        }
+    }
+}
+
+// The top of a platform is at `y`, and solid platforms
+// extend `depth` pixels below it.
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlatformData {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    #[serde(default)]
+    pub depth: i32,
+    pub kind: PlatformKind,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnPoint {
+    pub x: i32,
+    pub y: i32,
+    pub facing: Orientation,
+}
+
+impl SpawnPoint {
+    pub fn position(&self) -> Position {
+        Position {
+            x: self.x,
+            y: self.y,
+        }
+    }
+}
+
+// Everything about where a match is played. Once the match starts
+// the stage is also a resource, as it never changes.
+#[derive(Debug, Clone, Resource, TypeUuid, TypePath)]
+#[uuid = "0d5a4f7e-8c1b-4b8e-a3f2-5e9c7d6b1a40"]
+pub struct Stage {
+    pub id: StageId,
+    // Relative to the asset folder.
+    pub background: String,
+    // The area the camera always keeps in view.
+    pub camera: Bounds,
+    // Fighters whose position leaves this are knocked out.
+    pub blast_zone: Bounds,
+    pub platforms: Vec<PlatformData>,
+    // Indexed by player handle, with one for each player a match
+    // can have.
+    pub spawns: Vec<SpawnPoint>,
+    pub respawns: Vec<SpawnPoint>,
+}
+
+impl Stage {
+    pub fn spawn_point(&self, handle: usize) -> SpawnPoint {
+        self.spawns[handle]
+    }
+
+    pub fn respawn_point(&self, handle: usize) -> SpawnPoint {
+        self.respawns[handle]
+    }
+
+    pub fn from_yaml(bytes: &[u8]) -> Result<Self, StageError> {
+        let raw: RawStage = serde_yaml::from_slice(bytes).map_err(StageError::Parse)?;
+        raw.validate()
+    }
+}
+
+#[derive(Debug)]
+pub enum StageError {
+    Parse(serde_yaml::Error),
+    UnknownStage(String),
+    MissingSpawns(usize),
+    MissingRespawns(usize),
+    EmptyBlastZone,
+    EmptyPlatform(usize),
+    OutsideBlastZone { x: i32, y: i32 },
+}
+
+impl fmt::Display for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use StageError as E;
+        match self {
+            E::Parse(err) => write!(f, "could not parse stage: {err}"),
+            E::UnknownStage(name) => write!(f, "unknown stage `{name}`"),
+            E::MissingSpawns(count) => write!(
+                f,
+                "stage has {count} spawn points rather than one for each of {MAX_PLAYERS} players"
+            ),
+            E::MissingRespawns(count) => write!(
+                f,
+                "stage has {count} respawn points rather than one for each of {MAX_PLAYERS} players"
+            ),
+            E::EmptyBlastZone => write!(f, "the blast zone has no area"),
+            E::EmptyPlatform(index) => write!(
+                f,
+                "platform {index} has no width, or is solid and has no depth"
+            ),
+            E::OutsideBlastZone { x, y } => {
+                write!(f, "the point ({x}, {y}) is outside of the blast zone")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StageError {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawStage {
+    name: String,
+    background: String,
+    camera: Bounds,
+    blast_zone: Bounds,
+    platforms: Vec<PlatformData>,
+    spawns: Vec<SpawnPoint>,
+    respawns: Vec<SpawnPoint>,
+}
+
+impl RawStage {
+    fn validate(self) -> Result<Stage, StageError> {
+        let id = StageId::from_name(&self.name).ok_or(StageError::UnknownStage(self.name))?;
+        if self.spawns.len() != MAX_PLAYERS {
+            return Err(StageError::MissingSpawns(self.spawns.len()));
+        }
+        if self.respawns.len() != MAX_PLAYERS {
+            return Err(StageError::MissingRespawns(self.respawns.len()));
+        }
+        if self.blast_zone.width <= 0 || self.blast_zone.height <= 0 {
+            return Err(StageError::EmptyBlastZone);
+        }
+        for (index, platform) in self.platforms.iter().enumerate() {
+            let needs_depth = platform.kind == PlatformKind::Solid;
+            if platform.width <= 0 || (needs_depth && platform.depth <= 0) {
+                return Err(StageError::EmptyPlatform(index));
+            }
+        }
+        for point in self.spawns.iter().chain(&self.respawns) {
+            if !self.blast_zone.contains(&point.position()) {
+                return Err(StageError::OutsideBlastZone {
+                    x: point.x,
+                    y: point.y,
+                });
+            }
+        }
+        log::trace!("Loaded stage {}", id.name());
+        Ok(Stage {
+            id,
+            background: self.background,
+            camera: self.camera,
+            blast_zone: self.blast_zone,
+            platforms: self.platforms,
+            spawns: self.spawns,
+            respawns: self.respawns,
+        })
+    }
+}
+
+#[derive(Default)]
+pub struct StageLoader;
+
+impl AssetLoader for StageLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let stage = Stage::from_yaml(bytes).map_err(|err| {
+                log::error!("{:?}: {err}", load_context.path());
+                err
+            })?;
+            load_context.set_default_asset(LoadedAsset::new(stage));
+            Ok(())
+        })
+    }
+
+    // Stages are YAML like frame data, so they need an extension
+    // of their own to be told apart.
+    fn extensions(&self) -> &[&str] {
+        &["stage.yaml"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The platform stage, to be broken one field at a time.
+    const PLATFORM: &str = "
+name: platform
+background: stages/platform.png
+camera: { x: -360, y: -360, width: 720, height: 720 }
+blast_zone: { x: -720, y: -720, width: 1440, height: 1440 }
+platforms:
+  - { x: -50, y: 0, width: 100, kind: Soft }
+spawns: [{ x: -25, y: 86, facing: Right }, { x: 25, y: 86, facing: Left },
+         { x: -40, y: 176, facing: Right }, { x: 40, y: 176, facing: Left }]
+respawns: [{ x: 0, y: 90, facing: Right }, { x: 0, y: 90, facing: Left },
+           { x: 0, y: 180, facing: Right }, { x: 0, y: 180, facing: Left }]
+";
+
+    // The platform stage with `field` swapped out for `value`.
+    fn stage_error(field: &str, value: &str) -> StageError {
+        let mut raw: serde_yaml::Mapping = serde_yaml::from_str(PLATFORM).unwrap();
+        raw.insert(field.into(), serde_yaml::from_str(value).unwrap());
+        let raw: RawStage = serde_yaml::from_value(raw.into()).unwrap();
+        raw.validate().unwrap_err()
+    }
+
+    #[test]
+    fn every_player_needs_a_spawn_point() {
+        let err = stage_error("spawns", "[{ x: 0, y: 86, facing: Right }]");
+        assert!(matches!(err, StageError::MissingSpawns(1)), "{err}");
+        let err = stage_error("spawns", "[]");
+        assert!(matches!(err, StageError::MissingSpawns(0)), "{err}");
+    }
+
+    #[test]
+    fn every_player_needs_a_respawn_point() {
+        let err = stage_error(
+            "respawns",
+            "[{ x: 0, y: 90, facing: Right }, { x: 0, y: 90, facing: Left }]",
+        );
+        assert!(matches!(err, StageError::MissingRespawns(2)), "{err}");
+    }
+
+    #[test]
+    fn blast_zones_must_have_an_area() {
+        let err = stage_error("blast_zone", "{ x: -720, y: -720, width: 0, height: 1440 }");
+        assert!(matches!(err, StageError::EmptyBlastZone), "{err}");
+        let err = stage_error(
+            "blast_zone",
+            "{ x: 720, y: 720, width: -1440, height: -1440 }",
+        );
+        assert!(matches!(err, StageError::EmptyBlastZone), "{err}");
+    }
+
+    #[test]
+    fn spawn_points_must_be_inside_the_blast_zone() {
+        let err = stage_error(
+            "blast_zone",
+            "{ x: -720, y: -720, width: 1440, height: 720 }",
+        );
+        assert!(
+            matches!(err, StageError::OutsideBlastZone { x: -25, y: 86 }),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn platforms_need_width_and_solid_ones_depth() {
+        let err = stage_error("platforms", "[{ x: -50, y: 0, width: 0, kind: Soft }]");
+        assert!(matches!(err, StageError::EmptyPlatform(0)), "{err}");
+        let err = stage_error(
+            "platforms",
+            "[{ x: -50, y: 0, width: 100, kind: Soft }, { x: -50, y: 0, width: 100, kind: Solid }]",
+        );
+        assert!(matches!(err, StageError::EmptyPlatform(1)), "{err}");
+    }
+}
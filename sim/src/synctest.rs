@@ -0,0 +1,150 @@
+use crate::checksum::{
+    dump_world, Checksum, FighterChecksumQuery, ProjectileChecksumQuery, HISTORY,
+};
+use crate::world::FrameCount;
+use bevy::log;
+use bevy::prelude::*;
+
+use std::collections::BTreeMap;
+
+// The checksum of every frame the sync test has simulated, to check
+// resimulations of the frame against.
+#[derive(Resource, Default, Debug)]
+pub struct SyncTestLog(BTreeMap<u32, u64>);
+
+// Runs last in the GgrsSchedule. The first time a frame is simulated its
+// checksum is recorded, and every resimulation of it has to match.
+pub fn verify_system(
+    frame: Res<FrameCount>,
+    checksum: Res<Checksum>,
+    mut history: ResMut<SyncTestLog>,
+    query: FighterChecksumQuery,
+    projectiles: ProjectileChecksumQuery,
+) {
+    let checksum = checksum.0;
+    match history.0.get(&frame.0) {
+        Some(&expected) if expected != checksum => {
+            panic!(
+                "Sync test failed: frame {} resimulated with checksum {checksum:#x} \
+                 instead of {expected:#x}, fighters are now\n{}",
+                frame.0,
+                dump_world(&query, &projectiles)
+            );
+        }
+        Some(_) => log::trace!("Frame {} resimulated identically", frame.0),
+        None => {
+            history.0.insert(frame.0, checksum);
+            history.0 = history.0.split_off(&frame.0.saturating_sub(HISTORY));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::headless;
+    use crate::input::InputSource;
+    use crate::stage::StageId;
+    use crate::world::CharacterId;
+
+    // Longer than the default so that a desync has more
+    // frames in which to show up.
+    const CHECK_DISTANCE: usize = 7;
+
+    const MATCHUPS: [&[CharacterId]; 4] = [
+        &[CharacterId::Postbox, CharacterId::Postbox],
+        &[CharacterId::Postbox, CharacterId::Celeste],
+        &[CharacterId::Celeste, CharacterId::Celeste],
+        &[
+            CharacterId::Postbox,
+            CharacterId::Celeste,
+            CharacterId::Celeste,
+            CharacterId::Postbox,
+        ],
+    ];
+
+    #[test]
+    fn random_inputs_resimulate_identically() {
+        for stage in StageId::ALL {
+            for (seed, characters) in MATCHUPS.into_iter().enumerate() {
+                let inputs = InputSource::random(seed as u32 + 1);
+                let mut app = headless::synctest_app(characters, stage, inputs, CHECK_DISTANCE);
+                headless::run_frames(&mut app, 600);
+            }
+        }
+    }
+
+    // Walks the players into each other and through every move, dashes
+    // and runs, then shields, rolls and dodges on the ground and in the air.
+    const SCRIPT: &str = "
+        20 R    L
+        10 D    -
+        10 DR   DL
+        1  J    J
+        10 -    -
+        1  H    H
+        20 -    -
+        1  RH   LH
+        20 -    -
+        1  UH   UH
+        20 -    -
+        1  DH   DH
+        20 -    -
+        1  J    J
+        4  -    -
+        1  H    H
+        20 R    L
+        1  J    J
+        1  RH   LH
+        20 -    -
+        1  J    -
+        1  LH   -
+        20 -    -
+        1  UH   -
+        20 -    -
+        1  DH   -
+        20 -    -
+        1  RFH  LFH
+        20 H    H
+        30 -    -
+        1  UFH  UFH
+        30 -    -
+        1  DFH  DFH
+        30 -    -
+        20 RF   LF
+        10 -    -
+        60 -    -
+        1  H    H
+        10 -    -
+        1  H    H
+        5  -    -
+        1  H    H
+        60 -    -
+        1  B    B
+        20 -    -
+        1  J    J
+        4  -    -
+        1  B    B
+        60 -    -
+        10 S    S
+        1  SL   SR
+        30 -    -
+        10 S    S
+        1  SD   SD
+        30 -    -
+        1  J    J
+        4  -    -
+        1  S    S
+        60 -    -
+    ";
+
+    #[test]
+    fn every_stance_resimulates_identically() {
+        for stage in StageId::ALL {
+            for characters in MATCHUPS {
+                let inputs = InputSource::parse_script(SCRIPT).unwrap();
+                let mut app = headless::synctest_app(characters, stage, inputs, CHECK_DISTANCE);
+                headless::run_frames(&mut app, 750);
+            }
+        }
+    }
+}
@@ -3,10 +3,14 @@ use bevy::prelude::*;
 
 use std::net::SocketAddr;
 
+// Players in a match unless told otherwise, and the most there can be.
+pub const DEFAULT_PLAYERS: usize = 2;
+pub const MAX_PLAYERS: usize = 4;
+
 #[derive(Debug)]
 pub struct GgrsConfig;
 
-#[derive(Resource, Reflect, Default, Debug)]
+#[derive(Resource, Reflect, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct PlayerId(pub usize);
 
 impl ggrs::Config for GgrsConfig {
@@ -0,0 +1,433 @@
+use bevy::log;
+use bevy::prelude::*;
+use bevy_ggrs::AddRollbackCommandExtension;
+use bytemuck::{Pod, Zeroable};
+use serde::Deserialize;
+
+use std::default::Default;
+
+use crate::input::BUTTONS;
+use crate::machine::celeste::CelesteState;
+use crate::machine::postbox::PostboxState;
+use crate::machine::types::{Armour, Physics};
+use crate::shield::Shield;
+use crate::stage::{SpawnPoint, Stage};
+use crate::types::*;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CharacterId {
+    #[default]
+    Postbox,
+    Celeste,
+}
+
+impl CharacterId {
+    pub const ALL: [CharacterId; 2] = [CharacterId::Postbox, CharacterId::Celeste];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CharacterId::Postbox => "Postbox",
+            CharacterId::Celeste => "Celeste",
+        }
+    }
+
+    pub fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|character| *character == self)
+            .unwrap()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|character| character.name().eq_ignore_ascii_case(name))
+    }
+}
+
+// The character each player fights as, indexed by player handle.
+#[derive(Resource, Debug, Default)]
+pub struct CharacterChoices(pub Vec<CharacterId>);
+
+#[derive(Component, Reflect, Default)]
+pub struct Fighter {}
+
+#[derive(Component, Reflect, Default)]
+pub struct DoesDamage {}
+
+#[derive(Component, Reflect, Default)]
+pub struct Environment {}
+
+#[derive(Component, Reflect, Default)]
+pub struct CollisionRect {
+    pub width: i32,
+    pub height: i32,
+}
+
+// An axis-aligned box, given relative to a fighter's Position
+// for a fighter which is facing right.
+#[derive(Copy, Clone, Default, Reflect, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub struct Bounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Bounds {
+    // Places the box in the world around a fighter.
+    pub fn at(&self, pos: &Position, orientation: Orientation) -> Bounds {
+        let x = match orientation {
+            Orientation::Right => self.x,
+            Orientation::Left => -(self.x + self.width),
+        };
+        Bounds {
+            x: pos.x + x,
+            y: pos.y + self.y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    // Includes the edges of the box.
+    pub fn contains(&self, pos: &Position) -> bool {
+        (self.x..=self.x + self.width).contains(&pos.x)
+            && (self.y..=self.y + self.height).contains(&pos.y)
+    }
+
+    pub fn overlaps(&self, other: &Bounds) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
+// Where a fighter can be hit, relative to its Position. It covers
+// the fighter's body, so unlike a hitbox it doesn't turn around.
+#[derive(Component, Reflect, Default, Debug)]
+pub struct Hurtbox(pub Bounds);
+
+impl Hurtbox {
+    pub fn at(&self, pos: &Position) -> Bounds {
+        Bounds {
+            x: pos.x + self.0.x,
+            y: pos.y + self.0.y,
+            ..self.0
+        }
+    }
+}
+
+// The player handles a fighter has already hit during its current move,
+// so that a hitbox which stays out for several frames only hits once.
+#[derive(Component, Reflect, Default, Debug, Hash, Clone)]
+pub struct HitRegistry {
+    hit: u8,
+}
+
+impl HitRegistry {
+    pub fn clear(&mut self) {
+        self.hit = 0;
+    }
+
+    pub fn has_hit(&self, handle: &PlayerId) -> bool {
+        self.hit & (1 << handle.0) != 0
+    }
+
+    pub fn register(&mut self, handle: &PlayerId) {
+        self.hit |= 1 << handle.0;
+    }
+}
+
+#[derive(Copy, Clone, Default, Reflect, Debug, PartialEq, Eq, Hash)]
+pub struct PlatformId(pub(crate) u8);
+
+// Soft platforms can be jumped up through and dropped through by
+// pressing down, while solid platforms are the body of the stage,
+// reaching `depth` below their top and blocking fighters from every side.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Deserialize)]
+pub enum PlatformKind {
+    #[default]
+    Soft,
+    Solid,
+}
+
+#[derive(Debug, Component)]
+pub struct Platform {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub depth: i32,
+    pub kind: PlatformKind,
+    pub id: PlatformId,
+}
+
+impl Platform {
+    // Whether a fighter at `pos` is over some part of the platform.
+    pub fn is_under(&self, pos: &Position) -> bool {
+        pos.x < self.x + self.width && pos.x + FIGHTER_DIMENSIONS > self.x
+    }
+
+    // Only solid platforms have ledges.
+    pub fn ledges(&self) -> Vec<Ledge> {
+        match self.kind {
+            PlatformKind::Soft => Vec::new(),
+            PlatformKind::Solid => [Orientation::Left, Orientation::Right]
+                .into_iter()
+                .map(|side| Ledge {
+                    platform: self.id,
+                    side,
+                })
+                .collect(),
+        }
+    }
+
+    // The space solid platforms keep fighters out of.
+    pub fn solid_bounds(&self) -> Option<Bounds> {
+        match self.kind {
+            PlatformKind::Soft => None,
+            PlatformKind::Solid => Some(Bounds {
+                x: self.x,
+                y: self.y - self.depth,
+                width: self.width,
+                height: self.depth,
+            }),
+        }
+    }
+}
+
+pub const FIGHTER_DIMENSIONS: i32 = 40;
+
+// The space a fighter takes up for colliding with the stage, standing
+// on `pos`.
+pub fn fighter_body(pos: &Position) -> Bounds {
+    Bounds {
+        x: pos.x,
+        y: pos.y,
+        width: FIGHTER_DIMENSIONS,
+        height: FIGHTER_DIMENSIONS,
+    }
+}
+
+pub fn fighter_is_on_plat(pos: &Position, plat: &Platform) -> bool {
+    if plat.is_under(pos) && pos.y - 1 < plat.y + 1 && pos.y > plat.y {
+        log::trace!("Character at {:?} standing on platform at {:?}", pos, plat);
+        true
+    } else {
+        log::trace!(
+            "Character at {:?} not standing on platform at {:?}",
+            pos,
+            plat
+        );
+        false
+    }
+}
+
+#[derive(Component, Reflect, Default, Debug, Hash, Clone)]
+pub struct Allegiance {
+    pub handle: PlayerId,
+}
+
+#[derive(Component, Reflect, Default, Debug, Hash, Clone)]
+pub struct Stocks {
+    pub count: u8,
+}
+
+#[derive(Component, Reflect, Default, Debug, Hash, Clone)]
+pub struct Damage {
+    pub percent: u16,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u16)]
+pub enum ButtonDiff {
+    NotHeld = 0,
+    Held = 1,
+    Released = 2,
+    Pressed = 3,
+}
+
+pub fn is_being_pressed(diff: ButtonDiff) -> bool {
+    diff == ButtonDiff::Held || diff == ButtonDiff::Pressed
+}
+
+// How each button changed since the last frame, two bits a button,
+// and where the stick has been.
+#[derive(Default, Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Pod, Zeroable, Reflect)]
+#[repr(C)]
+pub struct InputDiff {
+    pub buttons: u16,
+    pub stick: Stick,
+}
+
+// Frames each button's last press has left to count for, so that a
+// press made while the fighter can't act is acted on as soon as it can.
+// Indexed by `Button`.
+#[derive(Default, Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct InputBuffer {
+    pub frames_left: [u8; BUTTONS],
+}
+
+// The stick's position, and how many frames each axis has been away
+// from the middle, which tells a flick of the stick from a push.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Pod, Zeroable, Reflect)]
+#[repr(C)]
+pub struct Stick {
+    pub x: i8,
+    pub y: i8,
+    pub x_travel: u8,
+    pub y_travel: u8,
+}
+
+// Rather than use a floating-point transform system,
+// the game logic uses integers. This is translated to
+// floats for the graphics system.
+#[derive(Debug, Component, Reflect, Default, Hash, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Component, Reflect, Default)]
+pub struct Moving {}
+
+#[derive(Debug, Component, Reflect, Default, Hash, Clone)]
+pub struct Velocity {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Component, Reflect, Default)]
+pub struct Accelerating {}
+
+#[derive(Debug, Component, Reflect, Default, Hash, Clone)]
+pub struct Acceleration {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Component, PartialEq, Eq, Hash, Copy, Clone, Debug, Reflect, Default, Deserialize)]
+pub enum Orientation {
+    Left,
+    #[default]
+    Right,
+}
+
+// Frames simulated since the match started. Rolled back along with
+// everything else, so it always names the frame being simulated.
+#[derive(Resource, Reflect, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[reflect(Resource)]
+pub struct FrameCount(pub u32);
+
+pub fn frame_count_system(mut frame: ResMut<FrameCount>) {
+    frame.0 += 1;
+}
+
+// Frames of hitstun a fighter has just been dealt, to be picked up
+// by the fighter's state machine on the next frame.
+#[derive(Component, Default, Reflect, Debug, Hash, Clone)]
+pub struct Hitstun {
+    pub frames: i8,
+}
+
+#[derive(Component, Default, Reflect, Debug, Hash, Clone)]
+pub struct StandingOn {
+    pub platform: PlatformId,
+}
+
+// One of the two top corners of a solid platform, on the `side`
+// of the platform it sticks out to.
+#[derive(Copy, Clone, Default, Reflect, Debug, PartialEq, Eq, Hash)]
+pub struct Ledge {
+    pub platform: PlatformId,
+    pub side: Orientation,
+}
+
+#[derive(Component, Default, Reflect, Debug, Hash, Clone)]
+pub struct HangingFrom {
+    pub ledge: Ledge,
+}
+
+// Frames until the fighter can grab a ledge again, so that letting go
+// of one doesn't grab straight back on to it.
+#[derive(Component, Default, Reflect, Debug, Hash, Clone)]
+pub struct LedgeCooldown {
+    pub frames: u8,
+}
+
+// The parts of a fighter which start over with each stock,
+// other than its state machine.
+pub fn fresh_stock(spawn: &SpawnPoint) -> impl Bundle {
+    (
+        (spawn.facing, Hitstun::default(), InputBuffer::default()),
+        (
+            Physics::default(),
+            Armour::default(),
+            LedgeCooldown::default(),
+            Shield::default(),
+            HitRegistry::default(),
+        ),
+        (
+            spawn.position(),
+            Velocity { x: 0, y: 0 },
+            Acceleration { x: 0, y: 0 },
+        ),
+        Damage { percent: 0 },
+    )
+}
+
+// Spawns everything about a fighter which the simulation needs,
+// leaving how it is drawn to the caller. Fighters drop from their
+// spawn point on to the stage.
+pub fn spawn_fighter(
+    commands: &mut Commands,
+    stage: &Stage,
+    handle: PlayerId,
+    character: CharacterId,
+) -> Entity {
+    log::debug!("Spawning player {:?} as {:?}", handle, character);
+    let spawn = stage.spawn_point(handle.0);
+    let mut fighter = commands.spawn((
+        (Fighter {}, Allegiance { handle }, InputDiff::default()),
+        fresh_stock(&spawn),
+        (Moving {}, Accelerating {}),
+        Stocks { count: 4 },
+        (
+            CollisionRect {
+                width: FIGHTER_DIMENSIONS,
+                height: FIGHTER_DIMENSIONS,
+            },
+            Hurtbox(fighter_body(&Position::default())),
+            DoesDamage {},
+        ),
+    ));
+    match character {
+        CharacterId::Postbox => fighter.insert(PostboxState::default()),
+        CharacterId::Celeste => fighter.insert(CelesteState::default()),
+    };
+    fighter.add_rollback().id()
+}
+
+// Spawns the stage's platforms, in the order they are listed, and
+// makes the stage available to the simulation.
+pub fn spawn_stage(commands: &mut Commands, stage: &Stage) -> Vec<Entity> {
+    log::debug!("Spawning stage {:?}", stage.id);
+    commands.insert_resource(stage.clone());
+    stage
+        .platforms
+        .iter()
+        .enumerate()
+        .map(|(index, platform)| {
+            commands
+                .spawn(Platform {
+                    x: platform.x,
+                    y: platform.y,
+                    width: platform.width,
+                    depth: platform.depth,
+                    kind: platform.kind,
+                    id: PlatformId(index as u8),
+                })
+                .id()
+        })
+        .collect()
+}